@@ -0,0 +1,107 @@
+//! Compares the two ways `proxy_request` can hand a large `_search`
+//! response to the webview:
+//!
+//! - parsed: `ResponseBody::parse`, then the envelope serialized for the IPC
+//! - raw: `EsResponse::into_raw_payload`, optionally validating the body
+//!
+//! Both call the shipped code. Only the work done in Rust is timed. The IPC
+//! transfer and the webview's `JSON.parse`, which both paths still pay, are
+//! not part of the numbers.
+//!
+//! Run with `cargo bench --bench passthrough`.
+
+use bytes::Bytes;
+use ruskview::response::{EsResponse, ResponseBody};
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+
+const HITS: usize = 10_000;
+const RUNS: usize = 10;
+const CONTENT_TYPE: &str = "application/json; charset=UTF-8";
+
+// A `size: 10000` search over log-like documents
+fn search_response() -> Bytes {
+    let hits: Vec<Value> = (0..HITS)
+        .map(|i| {
+            json!({
+                "_index": "logs-2024.06.01",
+                "_id": format!("doc-{}", i),
+                "_score": 1.0,
+                "_source": {
+                    "@timestamp": "2024-06-01T12:00:00.000Z",
+                    "host": { "name": format!("web-{:03}", i % 200), "ip": "10.0.0.1" },
+                    "http": { "method": "GET", "status": 200, "bytes": i * 17 },
+                    "url": format!("/api/v1/items/{}?expand=owner,tags", i),
+                    "message": "GET /api/v1/items completed in 12ms with status 200 for user agent Mozilla/5.0",
+                    "tags": ["prod", "eu-west-1", "api"],
+                }
+            })
+        })
+        .collect();
+    serde_json::to_vec(&json!({
+        "took": 42,
+        "timed_out": false,
+        "_shards": { "total": 5, "successful": 5, "skipped": 0, "failed": 0 },
+        "hits": { "total": { "value": HITS, "relation": "eq" }, "max_score": 1.0, "hits": hits }
+    }))
+    .unwrap()
+    .into()
+}
+
+fn response(body: ResponseBody, size: usize) -> EsResponse {
+    EsResponse {
+        status: 200,
+        headers: Default::default(),
+        elapsed_ms: 0,
+        size,
+        content_type: Some(CONTENT_TYPE.to_string()),
+        body,
+        method: "POST".to_string(),
+        path: "/logs-*/_search".to_string(),
+    }
+}
+
+// What `proxy_request` returns, serialized the way the IPC layer does
+fn parsed(bytes: &Bytes) -> usize {
+    let body = ResponseBody::parse(bytes, Some(CONTENT_TYPE));
+    serde_json::to_vec(&response(body, bytes.len()))
+        .unwrap()
+        .len()
+}
+
+fn raw(bytes: &Bytes, validate: bool) -> usize {
+    response(ResponseBody::Raw(bytes.clone()), bytes.len())
+        .into_raw_payload(validate)
+        .unwrap()
+        .len()
+}
+
+fn median(f: impl Fn() -> usize) -> Duration {
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let started = Instant::now();
+            std::hint::black_box(f());
+            started.elapsed()
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+fn main() {
+    let bytes = search_response();
+    println!(
+        "_search response with {} hits, {:.1} MiB",
+        HITS,
+        bytes.len() as f64 / (1024.0 * 1024.0)
+    );
+
+    let cases: [(&str, Box<dyn Fn() -> usize>); 3] = [
+        ("parsed", Box::new(|| parsed(&bytes))),
+        ("raw, validated", Box::new(|| raw(&bytes, true))),
+        ("raw", Box::new(|| raw(&bytes, false))),
+    ];
+    for (name, f) in cases {
+        println!("{:<16} {:>10.2?}", name, median(f));
+    }
+}
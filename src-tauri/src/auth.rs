@@ -1,11 +1,47 @@
 use crate::models::connection::ConnectionProfile;
 use anyhow::Result;
+use async_trait::async_trait;
 
-pub trait Authenticator {
+#[async_trait]
+pub trait Authenticator: Send + Sync {
     // We take a mutable reference to the request to modify it (add headers)
-    fn sign(&self, request: &mut http::Request<Vec<u8>>, profile: &ConnectionProfile)
-    -> Result<()>;
+    async fn sign(
+        &self,
+        request: &mut http::Request<Vec<u8>>,
+        profile: &ConnectionProfile,
+    ) -> Result<()>;
+
+    // Drops cached credentials so the next `sign` fetches fresh ones.
+    // `rejection` is the body of the response that may mean they expired.
+    // Returns false when there is nothing to refresh, i.e. retrying is pointless.
+    async fn invalidate(&self, _rejection: &[u8]) -> bool {
+        false
+    }
+
+    // Whether a response with this status may mean the credentials expired,
+    // in which case its body is read and passed to `invalidate`
+    fn is_expired_status(&self, status: u16) -> bool {
+        status == 401
+    }
+}
+
+// `http` is the profile's pooled client, for authenticators that make
+// requests of their own
+pub fn for_profile(
+    profile: &ConnectionProfile,
+    http: &reqwest::Client,
+) -> Option<Box<dyn Authenticator>> {
+    match profile.auth_type.as_str() {
+        "basic" => Some(Box::new(basic::BasicAuth)),
+        "iam" => Some(Box::new(aws::AwsSigV4Auth::default())),
+        "apikey" => Some(Box::new(api_key::ApiKeyAuth)),
+        "oauth2" => Some(Box::new(oauth2::OAuth2Auth::new(http.clone()))),
+        _ => None,
+    }
 }
 
+pub mod api_key;
 pub mod aws;
 pub mod basic;
+pub mod helper;
+pub mod oauth2;
@@ -0,0 +1,35 @@
+use super::Authenticator;
+use crate::models::connection::ConnectionProfile;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use base64::prelude::*;
+use http::header::AUTHORIZATION;
+
+pub struct ApiKeyAuth;
+
+#[async_trait]
+impl Authenticator for ApiKeyAuth {
+    async fn sign(
+        &self,
+        request: &mut http::Request<Vec<u8>>,
+        profile: &ConnectionProfile,
+    ) -> Result<()> {
+        let key = profile
+            .api_key
+            .as_deref()
+            .ok_or_else(|| anyhow!("Missing API Key"))?;
+
+        // With an id the key is the raw secret, otherwise it is the encoded form
+        // Elasticsearch hands out as `encoded`.
+        let encoded = match profile.api_key_id.as_deref().filter(|id| !id.is_empty()) {
+            Some(id) => BASE64_STANDARD.encode(format!("{}:{}", id, key)),
+            None => key.to_string(),
+        };
+
+        let header_value = format!("ApiKey {}", encoded);
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, header_value.parse()?);
+        Ok(())
+    }
+}
@@ -1,82 +1,472 @@
 use super::Authenticator;
 use crate::models::connection::ConnectionProfile;
 use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use aws_config::environment::EnvironmentVariableCredentialsProvider;
+use aws_config::profile::ProfileFileCredentialsProvider;
+use aws_config::sts::AssumeRoleProvider;
+use aws_config::{BehaviorVersion, Region};
 use aws_credential_types::Credentials;
+use aws_credential_types::provider::{ProvideCredentials, SharedCredentialsProvider};
 use aws_sigv4::http_request::{
-    SignableBody, SignableRequest, SigningParams, SigningSettings, sign,
+    PayloadChecksumKind, SignableBody, SignableRequest, SigningParams, SigningSettings, sign,
 };
 use aws_sigv4::sign::v4;
 use aws_smithy_runtime_api::client::identity::Identity;
 use sha2::{Digest, Sha256};
-use std::time::SystemTime;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{Mutex, OnceCell};
+use tracing::info;
 
-pub struct AwsSigV4Auth;
+// Temporary credentials are refreshed this long before they expire.
+const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+// Found in the body of a 403 for expired or mis-signed credentials, as
+// opposed to a plain permission error
+const EXPIRED_MARKERS: &[&str] = &[
+    "ExpiredToken",
+    "InvalidSignatureException",
+    "security token included in the request is expired",
+    "Signature expired",
+];
+// SHA-256 of an empty body
+const EMPTY_PAYLOAD_HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
 
+#[derive(Default)]
+pub struct AwsSigV4Auth {
+    provider: OnceCell<SharedCredentialsProvider>,
+    credentials: Mutex<Option<Credentials>>,
+    // False for static keys, which fetching again would not change
+    refreshable: AtomicBool,
+}
+
+impl AwsSigV4Auth {
+    async fn credentials(&self, profile: &ConnectionProfile) -> Result<Credentials> {
+        let mut cached = self.credentials.lock().await;
+        if let Some(credentials) = cached.as_ref()
+            && credentials
+                .expiry()
+                .is_none_or(|expiry| expiry > SystemTime::now() + REFRESH_MARGIN)
+        {
+            return Ok(credentials.clone());
+        }
+
+        self.refreshable
+            .store(is_refreshable(profile), Ordering::Relaxed);
+        let provider = self
+            .provider
+            .get_or_try_init(|| build_provider(profile))
+            .await?;
+        let credentials = provider.provide_credentials().await?;
+        *cached = Some(credentials.clone());
+        Ok(credentials)
+    }
+}
+
+// An explicit service name wins; otherwise OpenSearch Serverless endpoints
+// (*.aoss.amazonaws.com) sign as "aoss" and everything else as "es".
+fn service_name(profile: &ConnectionProfile, uri: &http::Uri) -> String {
+    if let Some(service) = profile.aws_service.as_deref().filter(|s| !s.is_empty()) {
+        return service.to_string();
+    }
+    let is_serverless = uri
+        .host()
+        .is_some_and(|h| h.ends_with(".aoss.amazonaws.com"));
+    if is_serverless { "aoss" } else { "es" }.to_string()
+}
+
+// Environment and ~/.aws credentials can change on disk, and assumed roles
+// hand out new temporary ones
+fn is_refreshable(profile: &ConnectionProfile) -> bool {
+    profile.credential_source.as_deref().unwrap_or("static") != "static"
+        || profile.role_arn.as_deref().is_some_and(|r| !r.is_empty())
+}
+
+// Base credentials come from the profile itself, the environment or a named
+// profile in ~/.aws; an optional role is then assumed on top of them.
+async fn build_provider(profile: &ConnectionProfile) -> Result<SharedCredentialsProvider> {
+    let source = profile.credential_source.as_deref().unwrap_or("static");
+    let base = match source {
+        "static" => {
+            let access_key = profile
+                .access_key
+                .as_deref()
+                .ok_or_else(|| anyhow!("Missing Access Key"))?;
+            let secret_key = profile
+                .secret_key
+                .as_deref()
+                .ok_or_else(|| anyhow!("Missing Secret Key"))?;
+            SharedCredentialsProvider::new(Credentials::new(
+                access_key,
+                secret_key,
+                profile.session_token.clone().filter(|t| !t.is_empty()),
+                None,
+                "ruskview",
+            ))
+        }
+        "env" => SharedCredentialsProvider::new(EnvironmentVariableCredentialsProvider::new()),
+        "profile" => {
+            let mut builder = ProfileFileCredentialsProvider::builder();
+            if let Some(name) = profile.aws_profile.as_deref().filter(|n| !n.is_empty()) {
+                builder = builder.profile_name(name);
+            }
+            SharedCredentialsProvider::new(builder.build())
+        }
+        other => return Err(anyhow!("Unknown credential source: {}", other)),
+    };
+
+    let Some(role_arn) = profile.role_arn.as_deref().filter(|r| !r.is_empty()) else {
+        return Ok(base);
+    };
+
+    info!("Assuming role {} for profile {}", role_arn, profile.name);
+    let region = Region::new(
+        profile
+            .region
+            .clone()
+            .unwrap_or_else(|| "us-east-1".to_string()),
+    );
+
+    let mut loader = aws_config::defaults(BehaviorVersion::latest())
+        .region(region.clone())
+        .credentials_provider(base.clone());
+    if let Some(endpoint) = profile.sts_endpoint.as_deref().filter(|e| !e.is_empty()) {
+        loader = loader.endpoint_url(endpoint);
+    }
+    let sdk_config = loader.load().await;
+
+    let mut builder = AssumeRoleProvider::builder(role_arn)
+        .region(region)
+        .session_name(
+            profile
+                .role_session_name
+                .clone()
+                .unwrap_or_else(|| "ruskview".to_string()),
+        )
+        .configure(&sdk_config);
+    if let Some(external_id) = profile.external_id.as_deref().filter(|e| !e.is_empty()) {
+        builder = builder.external_id(external_id);
+    }
+
+    Ok(SharedCredentialsProvider::new(
+        builder.build_from_provider(base).await,
+    ))
+}
+
+// Payload hash, over the body as sent (gzipped if it is)
+fn payload(profile: &ConnectionProfile, body: &[u8]) -> SignableBody<'static> {
+    if profile.unsigned_payload {
+        SignableBody::UnsignedPayload
+    } else if body.is_empty() {
+        SignableBody::Precomputed(EMPTY_PAYLOAD_HASH.to_string())
+    } else {
+        let mut hasher = Sha256::new();
+        hasher.update(body);
+        SignableBody::Precomputed(hex::encode(hasher.finalize()))
+    }
+}
+
+// Serverless requires x-amz-content-sha256, managed domains accept it
+fn signing_settings() -> SigningSettings {
+    let mut settings = SigningSettings::default();
+    settings.payload_checksum_kind = PayloadChecksumKind::XAmzSha256;
+    settings
+}
+
+// Signs the request in place as of `time`
+fn sign_at(
+    request: &mut http::Request<Vec<u8>>,
+    credentials: Credentials,
+    region: &str,
+    service: &str,
+    signable_body: SignableBody<'_>,
+    settings: SigningSettings,
+    time: SystemTime,
+) -> Result<()> {
+    // Create Identity
+    let expiry = credentials.expiry();
+    let identity = Identity::new(credentials, expiry);
+
+    // Construct v4 params
+    let v4_params = v4::SigningParams::builder()
+        .identity(&identity)
+        .region(region)
+        .name(service)
+        .time(time)
+        .settings(settings)
+        .build()?;
+
+    let signing_params = SigningParams::V4(v4_params);
+
+    // Prepare headers
+    let mut headers_vec = Vec::new();
+    for (k, v) in request.headers() {
+        if let Ok(v_str) = v.to_str() {
+            headers_vec.push((k.as_str(), v_str));
+        }
+    }
+
+    // Construct SignableRequest
+    let signable_request = SignableRequest::new(
+        request.method().as_str(),
+        request.uri().to_string(),
+        headers_vec.into_iter(),
+        signable_body,
+    )
+    .map_err(|e| anyhow::anyhow!(e))?;
+
+    let (signing_instructions, _signature) = sign(signable_request, &signing_params)?.into_parts();
+
+    // Apply instructions to the request
+    signing_instructions.apply_to_request_http1x(request);
+
+    Ok(())
+}
+
+#[async_trait]
 impl Authenticator for AwsSigV4Auth {
-    fn sign(
+    async fn sign(
         &self,
         request: &mut http::Request<Vec<u8>>,
         profile: &ConnectionProfile,
     ) -> Result<()> {
-        let access_key = profile
-            .access_key
-            .as_deref()
-            .ok_or_else(|| anyhow!("Missing Access Key"))?;
-        let secret_key = profile
-            .secret_key
-            .as_deref()
-            .ok_or_else(|| anyhow!("Missing Secret Key"))?;
+        let credentials = self.credentials(profile).await?;
         let region = profile.region.as_deref().unwrap_or("us-east-1");
+        let service = service_name(profile, request.uri());
+        let signable_body = payload(profile, request.body());
+        sign_at(
+            request,
+            credentials,
+            region,
+            &service,
+            signable_body,
+            signing_settings(),
+            SystemTime::now(),
+        )
+    }
 
-        // Calculate payload hash
-        let payload_hash = if request.body().is_empty() {
-            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".to_string()
-        } else {
-            let mut hasher = Sha256::new();
-            hasher.update(request.body());
-            hex::encode(hasher.finalize())
-        };
-
-        let signing_settings = SigningSettings::default();
-
-        // Create Credentials and Identity
-        let credentials = Credentials::new(access_key, secret_key, None, None, "ruskview");
-        let identity = Identity::new(credentials, None);
-
-        // Construct v4 params
-        let v4_params = v4::SigningParams::builder()
-            .identity(&identity)
-            .region(region)
-            .name("es")
-            .time(SystemTime::now())
-            .settings(signing_settings)
-            .build()?;
-
-        let signing_params = SigningParams::V4(v4_params);
-
-        // Prepare headers
-        let mut headers_vec = Vec::new();
-        for (k, v) in request.headers() {
-            if let Ok(v_str) = v.to_str() {
-                headers_vec.push((k.as_str(), v_str));
-            }
+    // Only credentials that are about to expire, or that AWS reported as
+    // expired, are fetched again; a new STS session would not fix a denial
+    async fn invalidate(&self, rejection: &[u8]) -> bool {
+        if !self.refreshable.load(Ordering::Relaxed) {
+            return false;
+        }
+        let mut cached = self.credentials.lock().await;
+        let expiring = cached
+            .as_ref()
+            .and_then(Credentials::expiry)
+            .is_some_and(|expiry| expiry <= SystemTime::now() + REFRESH_MARGIN);
+        let body = String::from_utf8_lossy(rejection);
+        let reported = EXPIRED_MARKERS.iter().any(|marker| body.contains(marker));
+        if !expiring && !reported {
+            return false;
         }
+        cached.take();
+        true
+    }
+
+    // AWS answers expired signatures and tokens with 403
+    fn is_expired_status(&self, status: u16) -> bool {
+        matches!(status, 401 | 403)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Credentials, region, service and time of the AWS SigV4 test suite
+    const SECRET_KEY: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+    // 2015-08-30T12:36:00Z
+    const SUITE_TIME: u64 = 1_440_938_160;
+
+    fn suite_credentials() -> Credentials {
+        Credentials::new("AKIDEXAMPLE", SECRET_KEY, None, None, "test")
+    }
+
+    fn request(method: &str, uri: &str) -> http::Request<Vec<u8>> {
+        http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Vec::new())
+            .unwrap()
+    }
 
-        // Construct SignableRequest
-        let signable_request = SignableRequest::new(
-            request.method().as_str(),
-            request.uri().to_string(),
-            headers_vec.into_iter(),
-            SignableBody::Precomputed(payload_hash),
+    fn sign_suite(
+        request: &mut http::Request<Vec<u8>>,
+        body: SignableBody<'_>,
+        settings: SigningSettings,
+    ) {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(SUITE_TIME);
+        sign_at(
+            request,
+            suite_credentials(),
+            "us-east-1",
+            "service",
+            body,
+            settings,
+            time,
         )
-        .map_err(|e| anyhow::anyhow!(e))?;
+        .unwrap();
+    }
 
-        let (signing_instructions, _signature) =
-            sign(signable_request, &signing_params)?.into_parts();
+    fn header<'a>(request: &'a http::Request<Vec<u8>>, name: &str) -> &'a str {
+        request.headers()[name].to_str().unwrap()
+    }
+
+    fn profile_with(unsigned_payload: bool, aws_service: Option<&str>) -> ConnectionProfile {
+        let mut profile: ConnectionProfile = serde_json::from_value(serde_json::json!({
+            "id": "aws",
+            "name": "aws",
+            "url": "https://example.amazonaws.com",
+            "auth_type": "iam",
+        }))
+        .unwrap();
+        profile.unsigned_payload = unsigned_payload;
+        profile.aws_service = aws_service.map(str::to_string);
+        profile
+    }
 
-        // Apply instructions to the request
-        signing_instructions.apply_to_request_http1x(request);
+    // get-vanilla
+    #[test]
+    fn signs_get_vanilla() {
+        let mut request = request("GET", "https://example.amazonaws.com/");
+        let body = SignableBody::Precomputed(EMPTY_PAYLOAD_HASH.to_string());
+        sign_suite(&mut request, body, SigningSettings::default());
+
+        assert_eq!(header(&request, "x-amz-date"), "20150830T123600Z");
+        assert_eq!(
+            header(&request, "authorization"),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    // post-vanilla
+    #[test]
+    fn signs_post_vanilla() {
+        let mut request = request("POST", "https://example.amazonaws.com/");
+        let body = SignableBody::Precomputed(EMPTY_PAYLOAD_HASH.to_string());
+        sign_suite(&mut request, body, SigningSettings::default());
+
+        assert_eq!(
+            header(&request, "authorization"),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+        );
+    }
+
+    // get-vanilla with our settings, which also sign x-amz-content-sha256
+    #[test]
+    fn signs_content_sha256_header() {
+        let profile = profile_with(false, None);
+        let mut request = request("GET", "https://example.amazonaws.com/");
+        let body = payload(&profile, request.body());
+        sign_suite(&mut request, body, signing_settings());
+
+        assert_eq!(header(&request, "x-amz-content-sha256"), EMPTY_PAYLOAD_HASH);
+        assert_eq!(
+            header(&request, "authorization"),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-content-sha256;x-amz-date, \
+             Signature=726c5c4879a6b4ccbbd3b24edbd6b8826d34f87450fbbf4e85546fc7ba9c1642"
+        );
+    }
+
+    #[test]
+    fn hashes_the_body_as_sent() {
+        let profile = profile_with(false, None);
+        let mut request = request("POST", "https://example.amazonaws.com/_search");
+        *request.body_mut() = br#"{"size":0}"#.to_vec();
+        let body = payload(&profile, request.body());
+        sign_suite(&mut request, body, signing_settings());
+
+        let expected = hex::encode(Sha256::digest(br#"{"size":0}"#));
+        assert_eq!(header(&request, "x-amz-content-sha256"), expected);
+    }
+
+    #[test]
+    fn signs_unsigned_payload() {
+        let profile = profile_with(true, None);
+        let mut request = request("GET", "https://example.amazonaws.com/");
+        let body = payload(&profile, request.body());
+        sign_suite(&mut request, body, signing_settings());
+
+        assert_eq!(header(&request, "x-amz-content-sha256"), "UNSIGNED-PAYLOAD");
+        assert_eq!(
+            header(&request, "authorization"),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-content-sha256;x-amz-date, \
+             Signature=9b02fb7b5d0076fa47a0adda28c71e74ba4588334bc0139b8cd6bb87f16afe16"
+        );
+    }
+
+    #[test]
+    fn detects_service_from_host() {
+        let profile = profile_with(false, None);
+        let managed: http::Uri = "https://search-logs-abc.us-east-1.es.amazonaws.com/"
+            .parse()
+            .unwrap();
+        let serverless: http::Uri = "https://abc123.us-east-1.aoss.amazonaws.com/"
+            .parse()
+            .unwrap();
+        assert_eq!(service_name(&profile, &managed), "es");
+        assert_eq!(service_name(&profile, &serverless), "aoss");
+
+        let explicit = profile_with(false, Some("aoss"));
+        assert_eq!(service_name(&explicit, &managed), "aoss");
+        let empty = profile_with(false, Some(""));
+        assert_eq!(service_name(&empty, &serverless), "aoss");
+    }
+
+    // Refreshable credentials cached with the given expiry
+    async fn cached_auth(expiry: Option<SystemTime>) -> AwsSigV4Auth {
+        let auth = AwsSigV4Auth::default();
+        auth.refreshable.store(true, Ordering::Relaxed);
+        *auth.credentials.lock().await = Some(Credentials::new(
+            "AKIDEXAMPLE",
+            SECRET_KEY,
+            Some("token".to_string()),
+            expiry,
+            "test",
+        ));
+        auth
+    }
+
+    #[tokio::test]
+    async fn keeps_credentials_on_permission_errors() {
+        let auth = cached_auth(Some(SystemTime::now() + Duration::from_secs(3600))).await;
+        let denied = br#"{"error":{"type":"security_exception","reason":"no permissions for [indices:data/read/search]"}}"#;
+        assert!(!auth.invalidate(denied).await);
+        assert!(auth.credentials.lock().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn refreshes_credentials_reported_as_expired() {
+        let auth = cached_auth(Some(SystemTime::now() + Duration::from_secs(3600))).await;
+        let expired = br#"{"message":"The security token included in the request is expired"}"#;
+        assert!(auth.invalidate(expired).await);
+        assert!(auth.credentials.lock().await.is_none());
+
+        let auth = cached_auth(None).await;
+        assert!(
+            auth.invalidate(br#"{"__type":"ExpiredTokenException"}"#)
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn refreshes_credentials_about_to_expire() {
+        let auth = cached_auth(Some(SystemTime::now() + Duration::from_secs(60))).await;
+        assert!(auth.invalidate(b"").await);
+    }
 
-        Ok(())
+    #[tokio::test]
+    async fn never_refreshes_static_keys() {
+        let auth = cached_auth(Some(SystemTime::now())).await;
+        auth.refreshable.store(false, Ordering::Relaxed);
+        assert!(!auth.invalidate(b"ExpiredToken").await);
     }
 }
@@ -1,13 +1,15 @@
 use super::Authenticator;
 use crate::models::connection::ConnectionProfile;
 use anyhow::Result;
+use async_trait::async_trait;
 use base64::prelude::*;
 use http::header::AUTHORIZATION;
 
 pub struct BasicAuth;
 
+#[async_trait]
 impl Authenticator for BasicAuth {
-    fn sign(
+    async fn sign(
         &self,
         request: &mut http::Request<Vec<u8>>,
         profile: &ConnectionProfile,
@@ -0,0 +1,141 @@
+use crate::models::connection::ConnectionProfile;
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tracing::info;
+
+const DEFAULT_TTL_SECS: u64 = 300;
+const HELPER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Secrets printed by a credential helper as a JSON object on stdout.
+/// Only the fields present override the profile.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HelperCredentials {
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub api_key_id: Option<String>,
+    pub api_key: Option<String>,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    pub session_token: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+impl HelperCredentials {
+    fn apply(self, profile: &mut ConnectionProfile) {
+        fn set(field: &mut Option<String>, value: Option<String>) {
+            if value.is_some() {
+                *field = value;
+            }
+        }
+        set(&mut profile.username, self.username);
+        set(&mut profile.password, self.password);
+        set(&mut profile.api_key_id, self.api_key_id);
+        set(&mut profile.api_key, self.api_key);
+        set(&mut profile.access_key, self.access_key);
+        set(&mut profile.secret_key, self.secret_key);
+        set(&mut profile.session_token, self.session_token);
+        set(&mut profile.client_secret, self.client_secret);
+    }
+}
+
+// Cached output of one helper and when it expires. Locked while the
+// helper runs, so concurrent resolves of a profile run it only once.
+type CacheSlot = Arc<Mutex<Option<(HelperCredentials, Instant)>>>;
+
+/// Runs the per-profile credential helper command and caches its output,
+/// keyed by profile id and command so editing the command takes effect.
+#[derive(Default)]
+pub struct CredentialHelper {
+    cache: Mutex<HashMap<(String, String), CacheSlot>>,
+}
+
+impl CredentialHelper {
+    /// Fills the profile's secrets from its helper, if one is configured.
+    pub async fn resolve(&self, mut profile: ConnectionProfile) -> Result<ConnectionProfile> {
+        let Some(command) = profile
+            .credential_helper
+            .clone()
+            .filter(|c| !c.trim().is_empty())
+        else {
+            return Ok(profile);
+        };
+
+        let key = (profile.id.clone(), command.clone());
+        let ttl = Duration::from_secs(profile.credential_helper_ttl.unwrap_or(DEFAULT_TTL_SECS));
+
+        // Only this profile waits on a slow helper
+        let slot = self.cache.lock().await.entry(key).or_default().clone();
+        let mut slot = slot.lock().await;
+        let credentials = match slot.as_ref() {
+            Some((credentials, expires_at)) if Instant::now() < *expires_at => credentials.clone(),
+            _ => {
+                let credentials = run_helper(&command, &profile).await?;
+                if !ttl.is_zero() {
+                    *slot = Some((credentials.clone(), Instant::now() + ttl));
+                }
+                credentials
+            }
+        };
+
+        credentials.apply(&mut profile);
+        Ok(profile)
+    }
+
+    pub async fn clear(&self) {
+        self.cache.lock().await.clear();
+    }
+
+    // Makes the next resolve for the profile run its helper again
+    pub async fn invalidate(&self, profile_id: &str) {
+        self.cache
+            .lock()
+            .await
+            .retain(|(id, _), _| id != profile_id);
+    }
+}
+
+async fn run_helper(command: &str, profile: &ConnectionProfile) -> Result<HelperCredentials> {
+    info!("Running credential helper for profile {}", profile.name);
+
+    let mut cmd = if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+
+    // The helper learns which profile it is asked about through the environment
+    cmd.env("RUSKVIEW_PROFILE_ID", &profile.id)
+        .env("RUSKVIEW_PROFILE_NAME", &profile.name)
+        .env("RUSKVIEW_URL", &profile.url)
+        .env("RUSKVIEW_AUTH_TYPE", &profile.auth_type)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let output = tokio::time::timeout(HELPER_TIMEOUT, cmd.output())
+        .await
+        .context("Credential helper timed out")?
+        .context("Failed to run credential helper")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "Credential helper exited with {}: {}",
+            output.status,
+            stderr.trim()
+        );
+    }
+
+    serde_json::from_slice(&output.stdout).context("Credential helper did not print valid JSON")
+}
@@ -1,100 +1,412 @@
-use crate::es::client::create_client;
+use crate::error::{AppError, ErrorKind};
+use crate::es::cancel::{self, InFlightRequest};
+use crate::es::client::{EsClient, RequestOptions, STREAM_CHANNEL_CAPACITY, create_client};
 use crate::es::cluster::ClusterRequest;
+use crate::es::http_pool::{PoolConfig, PoolStats};
 use crate::es::index::IndexRequest;
+use crate::es::node_pool::NodeState;
+use crate::es::response::{BodyChunk, EsResponse};
+use crate::es::scheduler::{ClusterMetrics, Priority, RequestJob};
+use crate::es::tunnel::SshTunnel;
+use crate::export::{self, ConflictPolicy, ImportPreviewEntry, ImportSummary};
+use crate::health::{self, SessionHealth};
+use crate::models::cloud_id::CloudId;
 use crate::models::connection::ConnectionProfile;
-use crate::state::AppState;
+use crate::state::{AppState, Session, SessionInfo};
+use crate::team_config;
 use std::collections::HashMap;
-use tauri::State;
-use tracing::info;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::{AppHandle, State};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+
+// Fills in helper-provided secrets and opens the SSH tunnel, if any,
+// before building the client
+pub async fn build_client(
+    profile: ConnectionProfile,
+    state: &AppState,
+) -> Result<Arc<dyn EsClient>, AppError> {
+    let profile = state
+        .credential_helper
+        .resolve(profile)
+        .await
+        .map_err(|e| AppError::auth(format!("Credential helper failed: {:#}", e)))?;
+
+    let tunnel = match profile.ssh_host.as_deref().filter(|h| !h.is_empty()) {
+        Some(_) => Some(SshTunnel::open(&profile).await.map_err(|e| {
+            AppError::new(ErrorKind::Tunnel, format!("SSH tunnel failed: {:#}", e))
+        })?),
+        None => None,
+    };
+    create_client(profile, tunnel, state.http_pool.clone())
+}
+
+// `GET /` through the scheduler, to check that a connection works
+async fn fetch_root(
+    client: &Arc<dyn EsClient>,
+    state: &AppState,
+) -> Result<serde_json::Value, AppError> {
+    state
+        .scheduler
+        .submit(RequestJob {
+            client: client.clone(),
+            priority: Priority::Interactive,
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            body: None,
+            options: RequestOptions::default(),
+        })
+        .await?
+        .into_json()
+}
 
 #[tauri::command]
 pub async fn test_connection(
     profile: ConnectionProfile,
-    _state: State<'_, AppState>,
-) -> Result<serde_json::Value, String> {
-    let client = create_client(profile);
-    client.proxy_request("GET", "/", None).await
+    state: State<'_, AppState>,
+) -> Result<serde_json::Value, AppError> {
+    state.touch();
+    // The form's profile may be unsaved or carry a placeholder id; without
+    // one the scheduler queues it by URL instead of with other tests
+    let unsaved = ConnectionProfile {
+        id: String::new(),
+        ..profile.clone()
+    };
+    let client = build_client(unsaved, &state).await?;
+    let mut result = fetch_root(&client, &state).await?;
+
+    // Show which deployment a Cloud ID resolved to
+    if let Some(cloud_id) = profile.cloud_id.as_deref().filter(|c| !c.is_empty())
+        && let Some(info) = result.as_object_mut()
+    {
+        let cloud = CloudId::parse(cloud_id).map_err(AppError::config)?;
+        info.insert(
+            "cloud_deployment".to_string(),
+            serde_json::to_value(cloud).map_err(AppError::config)?,
+        );
+    }
+
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn resolve_cloud_id(cloud_id: String) -> Result<CloudId, AppError> {
+    CloudId::parse(&cloud_id).map_err(AppError::config)
+}
+
+// The named session's client, or the default session's
+async fn session_client(
+    state: &AppState,
+    session_id: Option<&str>,
+) -> Result<Arc<dyn EsClient>, AppError> {
+    Ok(state.sessions.read().await.get(session_id)?.client.clone())
 }
 
+/// Opens a session on the profile's cluster, next to any already open.
+/// Connecting the same profile again replaces its session. The new session
+/// becomes the default for commands that do not pass a `session_id`, and
+/// is watched until closed: see `health::watch`.
 #[tauri::command]
 pub async fn connect_to_cluster(
     profile: ConnectionProfile,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<serde_json::Value, String> {
-    // Check cache first
-    {
-        let cache = state.connection_cache.read().await;
-        if let Some(expiry) = cache.get(&profile.id) {
-            if std::time::Instant::now() < *expiry {
-                // Cache hit and valid
-                let mut current = state.current_profile.write().await;
-                *current = Some(profile.clone());
-
-                // Update active client
-                let mut client_guard = state.active_client.write().await;
-                *client_guard = Some(create_client(profile.clone()));
-
-                // Return a mock success response or minimal info since we trust the cache
-                return Ok(serde_json::json!({
-                    "status": "connected_from_cache",
-                    "name": profile.name
-                }));
-            }
-        }
-    }
+) -> Result<SessionInfo, AppError> {
+    state.touch();
 
-    // First test the connection
-    let client = create_client(profile.clone());
-    let result = client.proxy_request("GET", "/", None).await?;
+    let client = build_client(profile.clone(), &state).await?;
+    let cluster = fetch_root(&client, &state).await?;
 
-    // If successful, update state and cache
-    {
-        let mut current = state.current_profile.write().await;
-        *current = Some(profile.clone());
-    }
+    // Unsaved profiles have no id to key the session by
+    let session_id = if profile.id.is_empty() {
+        uuid::Uuid::new_v4().to_string()
+    } else {
+        profile.id.clone()
+    };
+    let info = SessionInfo {
+        session_id,
+        name: profile.name.clone(),
+        url: profile.url.clone(),
+        connected_at: chrono::Utc::now().timestamp_millis() as u64,
+        cluster,
+        health: SessionHealth::connected(),
+    };
+    info!("Opened session {} for {}", info.session_id, info.name);
+    let session = state
+        .sessions
+        .write()
+        .await
+        .insert(Session::new(info.clone(), profile, client));
+    health::watch(app, session);
+    Ok(info)
+}
 
-    {
-        let mut client_guard = state.active_client.write().await;
-        *client_guard = Some(client);
-    }
+/// Open sessions, oldest first.
+#[tauri::command]
+pub async fn list_sessions(state: State<'_, AppState>) -> Result<Vec<SessionInfo>, AppError> {
+    Ok(state.sessions.read().await.list())
+}
 
-    {
-        let mut cache = state.connection_cache.write().await;
-        // Cache for 5 minutes
-        cache.insert(
-            profile.id,
-            std::time::Instant::now() + std::time::Duration::from_secs(300),
-        );
+/// Closes a session and stops its health watcher; its SSH tunnel, if any,
+/// closes once in-flight requests finish. Returns false when no such
+/// session is open.
+#[tauri::command]
+pub async fn disconnect(session_id: String, state: State<'_, AppState>) -> Result<bool, AppError> {
+    if state.sessions.write().await.remove(&session_id).is_none() {
+        return Ok(false);
     }
-
-    Ok(result)
+    info!("Closed session {}", session_id);
+    Ok(true)
 }
 
+/// Sends a request through `session_id`, or the default session. `request_id`
+/// lets the UI cancel it with `cancel_request` while it is queued or running;
+/// `priority` defaults to interactive. `timeout_ms` overrides the profile's
+/// request timeout, 60 seconds unless set, and 0 disables it. Only an
+/// explicit `timeout_ms` is also passed on to `_search` as its `timeout`.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn proxy_request(
     method: String,
     path: String,
     body: Option<serde_json::Value>,
+    request_id: Option<String>,
+    timeout_ms: Option<u64>,
+    priority: Option<Priority>,
+    session_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<serde_json::Value, String> {
-    let client_guard = state.active_client.read().await;
+) -> Result<EsResponse, AppError> {
+    let job = RequestJob {
+        client: session_client(&state, session_id.as_deref()).await?,
+        priority: priority.unwrap_or_default(),
+        method,
+        path,
+        body,
+        options: RequestOptions {
+            timeout: timeout_ms.map(Duration::from_millis),
+            ..RequestOptions::default()
+        },
+    };
+    send_request(&state, request_id, job).await
+}
 
-    if let Some(client) = client_guard.as_ref() {
-        client.proxy_request(&method, &path, body).await
-    } else {
-        Err("No active connection".to_string())
+/// Like `proxy_request`, but skips parsing the body in Rust and answers
+/// with binary IPC (see `EsResponse::into_raw_payload`). `validate`
+/// checks that a JSON or NDJSON body is well-formed first.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn proxy_request_raw(
+    method: String,
+    path: String,
+    body: Option<serde_json::Value>,
+    request_id: Option<String>,
+    timeout_ms: Option<u64>,
+    validate: Option<bool>,
+    session_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<tauri::ipc::Response, AppError> {
+    let job = RequestJob {
+        client: session_client(&state, session_id.as_deref()).await?,
+        priority: Priority::Interactive,
+        method: method,
+        path: path,
+        body,
+        options: RequestOptions {
+            timeout: timeout_ms.map(Duration::from_millis),
+            raw_body: true,
+            ..RequestOptions::default()
+        },
+    };
+    let payload = send_request(&state, request_id, job)
+        .await?
+        .into_raw_payload(validate.unwrap_or(false))?;
+    Ok(tauri::ipc::Response::new(payload))
+}
+
+/// Like `proxy_request`, but sends the body through `on_chunk` as it
+/// arrives: binary messages with the next piece of the body, each followed
+/// by a `{ received, expected }` progress message. Resolves to the envelope
+/// (with an empty `body`) once the body is complete, or fails if a piece
+/// could not be delivered. `expected` is null for compressed responses.
+/// `timeout_ms` limits the wait for each piece rather than the whole body.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn proxy_request_stream(
+    method: String,
+    path: String,
+    body: Option<serde_json::Value>,
+    request_id: Option<String>,
+    timeout_ms: Option<u64>,
+    on_chunk: Channel,
+    session_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<EsResponse, AppError> {
+    let (chunks, mut chunk_rx) = mpsc::channel::<BodyChunk>(STREAM_CHANNEL_CAPACITY);
+    let job = RequestJob {
+        client: session_client(&state, session_id.as_deref()).await?,
+        priority: Priority::Interactive,
+        method,
+        path,
+        body,
+        options: RequestOptions {
+            timeout: timeout_ms.map(Duration::from_millis),
+            chunks: Some(chunks),
+            ..RequestOptions::default()
+        },
+    };
+
+    // Ends once the scheduler drops the request and with it the sender, or
+    // at the first piece the UI did not get, since the body is then incomplete
+    let forward = async {
+        while let Some(chunk) = chunk_rx.recv().await {
+            let progress = serde_json::json!({
+                "received": chunk.received,
+                "expected": chunk.expected,
+            });
+            let sent = on_chunk
+                .send(InvokeResponseBody::Raw(chunk.data.to_vec()))
+                .and_then(|_| on_chunk.send(InvokeResponseBody::Json(progress.to_string())));
+            if let Err(e) = sent {
+                warn!("Failed to stream response chunk: {}", e);
+                return Some(e);
+            }
+        }
+        None
+    };
+    let (response, failed) = tokio::join!(send_request(&state, request_id, job), forward);
+    match failed {
+        Some(e) => Err(AppError::io(format!(
+            "Failed to stream the response: {}",
+            e
+        ))),
+        None => response,
     }
 }
 
+// Schedules a request, registered under `request_id` until it finishes so
+// `cancel_request` can abort it
+async fn send_request(
+    state: &AppState,
+    request_id: Option<String>,
+    mut job: RequestJob,
+) -> Result<EsResponse, AppError> {
+    state.touch();
+    let request_id = request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let opaque_id = cancel::opaque_id(&request_id);
+    job.options.opaque_id = Some(opaque_id.clone());
+
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    state.in_flight.lock().await.insert(
+        request_id.clone(),
+        InFlightRequest {
+            cancel: cancel_tx,
+            client: job.client.clone(),
+            opaque_id,
+            path: job.path.clone(),
+        },
+    );
+
+    let (method, path) = (job.method.clone(), job.path.clone());
+    // Dropping the scheduled future dequeues or aborts the request
+    let result = tokio::select! {
+        result = state.scheduler.submit(job) => result,
+        _ = cancel_rx => Err(
+            AppError::new(ErrorKind::Cancelled, "Request cancelled").with_request(&method, &path),
+        ),
+    };
+
+    state.in_flight.lock().await.remove(&request_id);
+    result
+}
+
+/// Aborts an in-flight `proxy_request` or `proxy_request_raw`; searches are also cancelled on the
+/// cluster. Returns false when the request already finished.
+#[tauri::command]
+pub async fn cancel_request(
+    request_id: String,
+    state: State<'_, AppState>,
+) -> Result<bool, AppError> {
+    let Some(request) = state.in_flight.lock().await.remove(&request_id) else {
+        return Ok(false);
+    };
+    let _ = request.cancel.send(());
+
+    if request.is_search() {
+        match cancel::cancel_tasks(&state.scheduler, &request.client, &request.opaque_id).await {
+            Ok(count) => info!("Cancelled {} cluster tasks for {}", count, request_id),
+            // The local request is gone either way
+            Err(e) => warn!("Failed to cancel cluster tasks for {}: {}", request_id, e),
+        }
+    }
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn get_node_pool(
+    session_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<NodeState>, AppError> {
+    Ok(session_client(&state, session_id.as_deref()).await?.nodes())
+}
+
+#[tauri::command]
+pub async fn get_scheduler_metrics(
+    state: State<'_, AppState>,
+) -> Result<Vec<ClusterMetrics>, AppError> {
+    Ok(state.scheduler.metrics())
+}
+
+/// Per-host HTTP pool statistics. The `estimated_*` connection counts are
+/// just that: connections are inferred from the local address of each
+/// response, and one the server closed stays counted until the idle timeout.
+#[tauri::command]
+pub async fn get_pool_stats(state: State<'_, AppState>) -> Result<PoolStats, AppError> {
+    Ok(state.http_pool.stats())
+}
+
+/// Saves the HTTP pool settings. They apply to connections made from now
+/// on; open ones keep their current clients.
+#[tauri::command]
+pub async fn set_pool_config(
+    config: PoolConfig,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    {
+        let db_guard = state.db.lock()?;
+        let db = db_guard
+            .as_ref()
+            .ok_or_else(|| AppError::database("Database not initialized"))?;
+        db.set_pool_config(&config).map_err(AppError::database)?;
+    }
+    state.http_pool.set_config(config);
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn perform_cluster_op(
     operation: String,
     params: HashMap<String, String>,
+    priority: Option<Priority>,
+    session_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
     info!("perform_cluster_op called with operation: {}", operation);
     let req = ClusterRequest::new(operation, params);
-    proxy_request(req.method().to_string(), req.build_path(), None, state).await
+    proxy_request(
+        req.method().to_string(),
+        req.build_path(),
+        None,
+        None,
+        None,
+        priority,
+        session_id,
+        state,
+    )
+    .await?
+    .into_json()
 }
 
 #[tauri::command]
@@ -103,43 +415,229 @@ pub async fn perform_index_op(
     index: String,
     params: HashMap<String, String>,
     body: Option<serde_json::Value>,
+    timeout_ms: Option<u64>,
+    session_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
     let req = IndexRequest::new(operation, index, params);
-    proxy_request(req.method().to_string(), req.build_path(), body, state).await
+    proxy_request(
+        req.method().to_string(),
+        req.build_path(),
+        body,
+        None,
+        timeout_ms,
+        None,
+        session_id,
+        state,
+    )
+    .await?
+    .into_json()
 }
 
 #[tauri::command]
 pub async fn save_profile(
     profile: ConnectionProfile,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let db_guard = state.db.lock().map_err(|_| "Failed to lock db")?;
+) -> Result<(), AppError> {
+    state.touch();
+    if profile.read_only || team_config::is_file_profile(&profile.id) {
+        return Err(AppError::config(
+            "Profiles from ruskview.toml are read-only",
+        ));
+    }
+
+    let db_guard = state.db.lock()?;
     if let Some(db) = db_guard.as_ref() {
-        db.save_profile(&profile).map_err(|e| e.to_string())?;
+        db.save_profile(&profile).map_err(AppError::database)?;
         Ok(())
     } else {
-        Err("Database not initialized".to_string())
+        Err(AppError::database("Database not initialized"))
     }
 }
 
 #[tauri::command]
-pub async fn get_profiles(state: State<'_, AppState>) -> Result<Vec<ConnectionProfile>, String> {
-    let db_guard = state.db.lock().map_err(|_| "Failed to lock db")?;
+pub async fn get_profiles(state: State<'_, AppState>) -> Result<Vec<ConnectionProfile>, AppError> {
+    state.touch();
+    let mut profiles = {
+        let db_guard = state.db.lock()?;
+        if let Some(db) = db_guard.as_ref() {
+            db.get_profiles().map_err(AppError::database)?
+        } else {
+            return Err(AppError::database("Database not initialized"));
+        }
+    };
+
+    // Shared profiles from ruskview.toml come last, marked read_only
+    profiles.extend(state.team_config.profiles().await);
+    Ok(profiles)
+}
+
+#[tauri::command]
+pub async fn delete_profile(id: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    if team_config::is_file_profile(&id) {
+        return Err(AppError::config(
+            "Profiles from ruskview.toml are read-only",
+        ));
+    }
+
+    let db_guard = state.db.lock()?;
     if let Some(db) = db_guard.as_ref() {
-        db.get_profiles().map_err(|e| e.to_string())
+        db.delete_profile(&id).map_err(AppError::database)?;
+        Ok(())
     } else {
-        Err("Database not initialized".to_string())
+        Err(AppError::database("Database not initialized"))
     }
 }
 
 #[tauri::command]
-pub async fn delete_profile(id: String, state: State<'_, AppState>) -> Result<(), String> {
-    let db_guard = state.db.lock().map_err(|_| "Failed to lock db")?;
+pub async fn get_store_status(state: State<'_, AppState>) -> Result<serde_json::Value, AppError> {
+    let db_guard = state.db.lock()?;
     if let Some(db) = db_guard.as_ref() {
-        db.delete_profile(&id).map_err(|e| e.to_string())?;
-        Ok(())
+        Ok(serde_json::json!({
+            "master_password": db.has_master_password().map_err(AppError::database)?,
+            "locked": db.is_locked(),
+            "idle_timeout_secs": db.idle_timeout().map_err(AppError::database)?.as_secs(),
+        }))
+    } else {
+        Err(AppError::database("Database not initialized"))
+    }
+}
+
+#[tauri::command]
+pub async fn unlock_store(password: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    state.touch();
+    let mut db_guard = state.db.lock()?;
+    if let Some(db) = db_guard.as_mut() {
+        db.unlock(&password).map_err(AppError::database)
     } else {
-        Err("Database not initialized".to_string())
+        Err(AppError::database("Database not initialized"))
     }
 }
+
+#[tauri::command]
+pub async fn lock_store(state: State<'_, AppState>) -> Result<(), AppError> {
+    state.lock_store().await.map_err(AppError::database)
+}
+
+#[tauri::command]
+pub async fn set_master_password(
+    current_password: Option<String>,
+    new_password: String,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    state.touch();
+    let mut db_guard = state.db.lock()?;
+    if let Some(db) = db_guard.as_mut() {
+        db.set_master_password(current_password.as_deref(), &new_password)
+            .map_err(AppError::database)
+    } else {
+        Err(AppError::database("Database not initialized"))
+    }
+}
+
+#[tauri::command]
+pub async fn set_idle_timeout(seconds: u64, state: State<'_, AppState>) -> Result<(), AppError> {
+    let db_guard = state.db.lock()?;
+    if let Some(db) = db_guard.as_ref() {
+        db.set_idle_timeout(Duration::from_secs(seconds))
+            .map_err(AppError::database)
+    } else {
+        Err(AppError::database("Database not initialized"))
+    }
+}
+
+#[tauri::command]
+pub async fn export_profiles(
+    ids: Vec<String>,
+    passphrase: String,
+    include_secrets: bool,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<usize, AppError> {
+    state.touch();
+    let profiles = {
+        let db_guard = state.db.lock()?;
+        let db = db_guard
+            .as_ref()
+            .ok_or_else(|| AppError::database("Database not initialized"))?;
+        db.get_profiles().map_err(AppError::database)?
+    };
+
+    // An empty selection exports everything
+    let selected: Vec<ConnectionProfile> = profiles
+        .into_iter()
+        .filter(|p| ids.is_empty() || ids.contains(&p.id))
+        .map(|p| {
+            if include_secrets {
+                p
+            } else {
+                p.without_secrets()
+            }
+        })
+        .collect();
+
+    let contents = export::encrypt_profiles(&selected, &passphrase).map_err(AppError::config)?;
+    std::fs::write(&path, contents).map_err(AppError::io)?;
+    info!("Exported {} profiles to {}", selected.len(), path);
+    Ok(selected.len())
+}
+
+#[tauri::command]
+pub async fn preview_import(
+    path: String,
+    passphrase: String,
+    conflict: ConflictPolicy,
+    state: State<'_, AppState>,
+) -> Result<Vec<ImportPreviewEntry>, AppError> {
+    state.touch();
+    let contents = std::fs::read_to_string(&path).map_err(AppError::io)?;
+    let incoming = export::decrypt_profiles(&contents, &passphrase).map_err(AppError::config)?;
+
+    let db_guard = state.db.lock()?;
+    let db = db_guard
+        .as_ref()
+        .ok_or_else(|| AppError::database("Database not initialized"))?;
+    let existing = db.get_profiles().map_err(AppError::database)?;
+    Ok(export::preview_import(&incoming, &existing, conflict))
+}
+
+#[tauri::command]
+pub async fn import_profiles(
+    path: String,
+    passphrase: String,
+    conflict: ConflictPolicy,
+    ids: Option<Vec<String>>,
+    state: State<'_, AppState>,
+) -> Result<ImportSummary, AppError> {
+    state.touch();
+    let contents = std::fs::read_to_string(&path).map_err(AppError::io)?;
+    let incoming = export::decrypt_profiles(&contents, &passphrase).map_err(AppError::config)?;
+
+    let db_guard = state.db.lock()?;
+    let db = db_guard
+        .as_ref()
+        .ok_or_else(|| AppError::database("Database not initialized"))?;
+    export::import_profiles(db, incoming, conflict, ids.as_deref()).map_err(AppError::database)
+}
+
+#[tauri::command]
+pub async fn set_team_config_path(
+    path: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    let path = path.filter(|p| !p.trim().is_empty());
+    {
+        let db_guard = state.db.lock()?;
+        let db = db_guard
+            .as_ref()
+            .ok_or_else(|| AppError::database("Database not initialized"))?;
+        db.set_team_config_path(path.as_deref())
+            .map_err(AppError::database)?;
+    }
+
+    state
+        .team_config
+        .set_path(path.map(PathBuf::from))
+        .await
+        .map_err(AppError::config)
+}
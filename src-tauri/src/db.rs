@@ -1,13 +1,83 @@
-use crate::models::connection::ConnectionProfile;
-use magic_crypt::{MagicCryptTrait, new_magic_crypt};
-use rusqlite::{Connection, Result, params};
+use crate::es::http_pool::PoolConfig;
+use crate::models::connection::{ConnectionProfile, CustomHeader};
+use anyhow::{Result, anyhow, bail};
+use argon2::Argon2;
+use magic_crypt::{MagicCrypt256, MagicCryptTrait, new_magic_crypt};
+use rusqlite::{Connection, OptionalExtension, params};
 use std::path::Path;
+use std::time::Duration;
 
 const DB_NAME: &str = "ruskview.db";
-const ENCRYPTION_KEY: &str = "ruskview-local-secret-key-2024";
+// Key used until a master password is set; rows written with it are
+// re-encrypted when the master password is first configured.
+const LEGACY_ENCRYPTION_KEY: &str = "ruskview-local-secret-key-2024";
+// Encrypted with the derived key to verify a master password on unlock
+const KEY_CHECK_PLAINTEXT: &str = "ruskview-key-check";
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 900;
+
+// Profile columns holding encrypted secrets
+const ENCRYPTED_COLUMNS: &[&str] = &[
+    "password",
+    "secret_key",
+    "api_key",
+    "client_secret",
+    "client_cert_password",
+    "session_token",
+    "proxy_password",
+];
+
+// Columns added to `profiles` after the initial schema. Missing ones are
+// appended on startup so existing databases keep working.
+const PROFILE_MIGRATIONS: &[(&str, &str)] = &[
+    ("api_key_id", "TEXT"),
+    ("api_key", "TEXT"),
+    ("token_url", "TEXT"),
+    ("client_id", "TEXT"),
+    ("client_secret", "TEXT"),
+    ("scope", "TEXT"),
+    ("ca_cert_path", "TEXT"),
+    ("client_cert_path", "TEXT"),
+    ("client_key_path", "TEXT"),
+    ("client_cert_password", "TEXT"),
+    ("tls_fingerprint", "TEXT"),
+    ("accept_invalid_certs", "INTEGER NOT NULL DEFAULT 0"),
+    ("credential_source", "TEXT"),
+    ("aws_profile", "TEXT"),
+    ("session_token", "TEXT"),
+    ("role_arn", "TEXT"),
+    ("role_session_name", "TEXT"),
+    ("external_id", "TEXT"),
+    ("sts_endpoint", "TEXT"),
+    ("aws_service", "TEXT"),
+    ("unsigned_payload", "INTEGER NOT NULL DEFAULT 0"),
+    ("cloud_id", "TEXT"),
+    ("credential_helper", "TEXT"),
+    ("credential_helper_ttl", "INTEGER"),
+    ("tags", "TEXT"),
+    ("ssh_host", "TEXT"),
+    ("ssh_port", "INTEGER"),
+    ("ssh_user", "TEXT"),
+    ("ssh_key_path", "TEXT"),
+    ("ssh_jump_hosts", "TEXT"),
+    ("proxy_url", "TEXT"),
+    ("proxy_username", "TEXT"),
+    ("proxy_password", "TEXT"),
+    ("no_proxy", "TEXT"),
+    ("headers", "TEXT"),
+    ("urls", "TEXT"),
+    ("sniff", "INTEGER NOT NULL DEFAULT 0"),
+    ("retry_policy", "TEXT"),
+    ("connect_timeout_ms", "INTEGER"),
+    ("request_timeout_ms", "INTEGER"),
+    ("max_concurrent_requests", "INTEGER"),
+    ("max_response_bytes", "INTEGER"),
+    ("compression", "TEXT"),
+];
 
 pub struct Database {
     conn: Connection,
+    // None while a master password is set but has not been entered
+    cipher: Option<MagicCrypt256>,
 }
 
 impl Database {
@@ -15,6 +85,14 @@ impl Database {
         let db_path = path.as_ref().join(DB_NAME);
         let conn = Connection::open(db_path)?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
+            [],
+        )?;
+
         conn.execute(
             "CREATE TABLE IF NOT EXISTS profiles (
                 id TEXT PRIMARY KEY,
@@ -30,11 +108,228 @@ impl Database {
             [],
         )?;
 
-        Ok(Database { conn })
+        Self::migrate(&conn)?;
+
+        let mut db = Database { conn, cipher: None };
+        if !db.has_master_password()? {
+            db.cipher = Some(new_magic_crypt!(LEGACY_ENCRYPTION_KEY, 256));
+        }
+        Ok(db)
+    }
+
+    fn migrate(conn: &Connection) -> Result<()> {
+        let mut stmt = conn.prepare("PRAGMA table_info(profiles)")?;
+        let existing = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for (column, column_type) in PROFILE_MIGRATIONS {
+            if !existing.iter().any(|c| c == column) {
+                conn.execute(
+                    &format!("ALTER TABLE profiles ADD COLUMN {} {}", column, column_type),
+                    [],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_meta(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn has_master_password(&self) -> Result<bool> {
+        Ok(self.get_meta("key_check")?.is_some())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.cipher.is_none()
+    }
+
+    pub fn idle_timeout(&self) -> Result<Duration> {
+        let secs = self
+            .get_meta("idle_timeout_secs")?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_IDLE_TIMEOUT_SECS);
+        Ok(Duration::from_secs(secs))
+    }
+
+    pub fn set_idle_timeout(&self, timeout: Duration) -> Result<()> {
+        self.set_meta("idle_timeout_secs", &timeout.as_secs().to_string())
+    }
+
+    pub fn pool_config(&self) -> Result<PoolConfig> {
+        Ok(self
+            .get_meta("pool_config")?
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn set_pool_config(&self, config: &PoolConfig) -> Result<()> {
+        self.set_meta("pool_config", &serde_json::to_string(config)?)
+    }
+
+    pub fn team_config_path(&self) -> Result<Option<String>> {
+        self.get_meta("team_config_path")
+    }
+
+    pub fn set_team_config_path(&self, path: Option<&str>) -> Result<()> {
+        match path {
+            Some(path) => self.set_meta("team_config_path", path),
+            None => {
+                self.conn
+                    .execute("DELETE FROM meta WHERE key = 'team_config_path'", [])?;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn unlock(&mut self, password: &str) -> Result<()> {
+        let cipher = self.verify_master_password(password)?;
+        self.cipher = Some(cipher);
+        Ok(())
+    }
+
+    /// Forgets the derived key. A no-op without a master password, since
+    /// the legacy key cannot be forgotten.
+    pub fn lock(&mut self) -> Result<()> {
+        if self.has_master_password()? {
+            self.cipher = None;
+        }
+        Ok(())
+    }
+
+    /// Sets or changes the master password, re-encrypting every stored
+    /// secret with the new key. `current` is required when one is already set.
+    pub fn set_master_password(&mut self, current: Option<&str>, new: &str) -> Result<()> {
+        if new.is_empty() {
+            bail!("Master password must not be empty");
+        }
+
+        let old_cipher = if self.has_master_password()? {
+            let current = current.ok_or_else(|| anyhow!("Current master password required"))?;
+            self.verify_master_password(current)?
+        } else {
+            new_magic_crypt!(LEGACY_ENCRYPTION_KEY, 256)
+        };
+
+        // Every install (and every password change) gets a fresh salt
+        let salt = uuid::Uuid::new_v4();
+        let new_cipher = new_magic_crypt!(derive_key(new, salt.as_bytes())?, 256);
+
+        let tx = self.conn.transaction()?;
+        for column in ENCRYPTED_COLUMNS {
+            let rows = {
+                let mut stmt = tx.prepare(&format!(
+                    "SELECT id, {} FROM profiles WHERE {} IS NOT NULL",
+                    column, column
+                ))?;
+                stmt.query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+            };
+
+            for (id, encrypted) in rows {
+                let plain = old_cipher
+                    .decrypt_base64_to_string(&encrypted)
+                    .map_err(|e| anyhow!("Failed to decrypt {} of {}: {}", column, id, e))?;
+                tx.execute(
+                    &format!("UPDATE profiles SET {} = ?1 WHERE id = ?2", column),
+                    params![new_cipher.encrypt_str_to_base64(&plain), id],
+                )?;
+            }
+        }
+
+        // Secret header values are encrypted inside the JSON `headers` column
+        let rows = {
+            let mut stmt =
+                tx.prepare("SELECT id, headers FROM profiles WHERE headers IS NOT NULL")?;
+            stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        for (id, stored) in rows {
+            let headers = decrypt_headers(&old_cipher, &stored)
+                .map_err(|e| anyhow!("Failed to decrypt headers of {}: {}", id, e))?;
+            tx.execute(
+                "UPDATE profiles SET headers = ?1 WHERE id = ?2",
+                params![encrypt_headers(&new_cipher, &headers)?, id],
+            )?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('kdf_salt', ?1)",
+            params![hex::encode(salt.as_bytes())],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('key_check', ?1)",
+            params![new_cipher.encrypt_str_to_base64(KEY_CHECK_PLAINTEXT)],
+        )?;
+        tx.commit()?;
+
+        self.cipher = Some(new_cipher);
+        Ok(())
+    }
+
+    fn verify_master_password(&self, password: &str) -> Result<MagicCrypt256> {
+        let salt = self
+            .get_meta("kdf_salt")?
+            .ok_or_else(|| anyhow!("No master password configured"))?;
+        let check = self
+            .get_meta("key_check")?
+            .ok_or_else(|| anyhow!("No master password configured"))?;
+
+        let cipher = new_magic_crypt!(derive_key(password, &hex::decode(salt)?)?, 256);
+        match cipher.decrypt_base64_to_string(&check) {
+            Ok(plain) if plain == KEY_CHECK_PLAINTEXT => Ok(cipher),
+            _ => bail!("Incorrect master password"),
+        }
+    }
+
+    fn cipher(&self) -> Result<&MagicCrypt256> {
+        self.cipher
+            .as_ref()
+            .ok_or_else(|| anyhow!("Profile store is locked"))
     }
 
     pub fn save_profile(&self, profile: &ConnectionProfile) -> Result<()> {
-        let mc = new_magic_crypt!(ENCRYPTION_KEY, 256);
+        let mc = self.cipher()?;
+
+        // Secrets of helper-backed profiles are fetched on demand, never stored
+        let stripped;
+        let profile = if profile
+            .credential_helper
+            .as_deref()
+            .is_some_and(|c| !c.trim().is_empty())
+        {
+            stripped = ConnectionProfile {
+                password: None,
+                api_key: None,
+                secret_key: None,
+                session_token: None,
+                client_secret: None,
+                ..profile.clone()
+            };
+            &stripped
+        } else {
+            profile
+        };
 
         let encrypted_password = profile
             .password
@@ -44,11 +339,43 @@ impl Database {
             .secret_key
             .as_ref()
             .map(|k| mc.encrypt_str_to_base64(k));
+        let encrypted_api_key = profile
+            .api_key
+            .as_ref()
+            .map(|k| mc.encrypt_str_to_base64(k));
+        let encrypted_client_secret = profile
+            .client_secret
+            .as_ref()
+            .map(|s| mc.encrypt_str_to_base64(s));
+        let encrypted_client_cert_password = profile
+            .client_cert_password
+            .as_ref()
+            .map(|p| mc.encrypt_str_to_base64(p));
+        let encrypted_session_token = profile
+            .session_token
+            .as_ref()
+            .map(|t| mc.encrypt_str_to_base64(t));
+        let encrypted_proxy_password = profile
+            .proxy_password
+            .as_ref()
+            .map(|p| mc.encrypt_str_to_base64(p));
 
         self.conn.execute(
             "INSERT OR REPLACE INTO profiles (
-                id, name, url, auth_type, username, password, region, access_key, secret_key
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                id, name, url, auth_type, username, password, region, access_key, secret_key,
+                api_key_id, api_key, token_url, client_id, client_secret, scope,
+                ca_cert_path, client_cert_path, client_key_path, client_cert_password,
+                tls_fingerprint, accept_invalid_certs, credential_source, aws_profile,
+                session_token, role_arn, role_session_name, external_id, sts_endpoint,
+                aws_service, unsigned_payload, cloud_id, credential_helper, credential_helper_ttl,
+                tags, ssh_host, ssh_port, ssh_user, ssh_key_path, ssh_jump_hosts,
+                proxy_url, proxy_username, proxy_password, no_proxy, headers, urls, sniff,
+                retry_policy, connect_timeout_ms, request_timeout_ms, max_concurrent_requests,
+                max_response_bytes, compression
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15,
+                ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30,
+                ?31, ?32, ?33, ?34, ?35, ?36, ?37, ?38, ?39, ?40, ?41, ?42, ?43, ?44, ?45, ?46,
+                ?47, ?48, ?49, ?50, ?51, ?52)",
             params![
                 profile.id,
                 profile.name,
@@ -58,34 +385,166 @@ impl Database {
                 encrypted_password,
                 profile.region,
                 profile.access_key,
-                encrypted_secret_key
+                encrypted_secret_key,
+                profile.api_key_id,
+                encrypted_api_key,
+                profile.token_url,
+                profile.client_id,
+                encrypted_client_secret,
+                profile.scope,
+                profile.ca_cert_path,
+                profile.client_cert_path,
+                profile.client_key_path,
+                encrypted_client_cert_password,
+                profile.tls_fingerprint,
+                profile.accept_invalid_certs,
+                profile.credential_source,
+                profile.aws_profile,
+                encrypted_session_token,
+                profile.role_arn,
+                profile.role_session_name,
+                profile.external_id,
+                profile.sts_endpoint,
+                profile.aws_service,
+                profile.unsigned_payload,
+                profile.cloud_id,
+                profile.credential_helper,
+                profile.credential_helper_ttl,
+                serde_json::to_string(&profile.tags)?,
+                profile.ssh_host,
+                profile.ssh_port,
+                profile.ssh_user,
+                profile.ssh_key_path,
+                serde_json::to_string(&profile.ssh_jump_hosts)?,
+                profile.proxy_url,
+                profile.proxy_username,
+                encrypted_proxy_password,
+                profile.no_proxy,
+                encrypt_headers(mc, &profile.headers)?,
+                serde_json::to_string(&profile.urls)?,
+                profile.sniff,
+                serde_json::to_string(&profile.retry)?,
+                profile.connect_timeout_ms,
+                profile.request_timeout_ms,
+                profile.max_concurrent_requests,
+                profile.max_response_bytes,
+                serde_json::to_string(&profile.compression)?
             ],
         )?;
         Ok(())
     }
 
     pub fn get_profiles(&self) -> Result<Vec<ConnectionProfile>> {
-        let mc = new_magic_crypt!(ENCRYPTION_KEY, 256);
-        let mut stmt = self.conn.prepare("SELECT id, name, url, auth_type, username, password, region, access_key, secret_key FROM profiles")?;
+        let mc = self.cipher()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, url, auth_type, username, password, region, access_key, secret_key,
+                api_key_id, api_key, token_url, client_id, client_secret, scope,
+                ca_cert_path, client_cert_path, client_key_path, client_cert_password,
+                tls_fingerprint, accept_invalid_certs, credential_source, aws_profile,
+                session_token, role_arn, role_session_name, external_id, sts_endpoint,
+                aws_service, unsigned_payload, cloud_id, credential_helper, credential_helper_ttl,
+                tags, ssh_host, ssh_port, ssh_user, ssh_key_path, ssh_jump_hosts,
+                proxy_url, proxy_username, proxy_password, no_proxy, headers, urls, sniff,
+                retry_policy, connect_timeout_ms, request_timeout_ms, max_concurrent_requests,
+                max_response_bytes, compression
+            FROM profiles",
+        )?;
 
         let profile_iter = stmt.query_map([], |row| {
-            let password: Option<String> = row.get(5)?;
-            let secret_key: Option<String> = row.get(8)?;
+            let password: Option<String> = row.get("password")?;
+            let secret_key: Option<String> = row.get("secret_key")?;
+            let api_key: Option<String> = row.get("api_key")?;
+            let client_secret: Option<String> = row.get("client_secret")?;
+            let client_cert_password: Option<String> = row.get("client_cert_password")?;
+            let session_token: Option<String> = row.get("session_token")?;
+            let tags: Option<String> = row.get("tags")?;
+            let ssh_jump_hosts: Option<String> = row.get("ssh_jump_hosts")?;
+            let proxy_password: Option<String> = row.get("proxy_password")?;
+            let headers: Option<String> = row.get("headers")?;
+            let urls: Option<String> = row.get("urls")?;
+            let retry_policy: Option<String> = row.get("retry_policy")?;
+            let compression: Option<String> = row.get("compression")?;
 
             let decrypted_password = password.and_then(|p| mc.decrypt_base64_to_string(&p).ok());
             let decrypted_secret_key =
                 secret_key.and_then(|k| mc.decrypt_base64_to_string(&k).ok());
+            let decrypted_api_key = api_key.and_then(|k| mc.decrypt_base64_to_string(&k).ok());
+            let decrypted_client_secret =
+                client_secret.and_then(|s| mc.decrypt_base64_to_string(&s).ok());
+            let decrypted_client_cert_password =
+                client_cert_password.and_then(|p| mc.decrypt_base64_to_string(&p).ok());
+            let decrypted_session_token =
+                session_token.and_then(|t| mc.decrypt_base64_to_string(&t).ok());
+            let decrypted_proxy_password =
+                proxy_password.and_then(|p| mc.decrypt_base64_to_string(&p).ok());
 
             Ok(ConnectionProfile {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                url: row.get(2)?,
-                auth_type: row.get(3)?,
-                username: row.get(4)?,
+                id: row.get("id")?,
+                name: row.get("name")?,
+                url: row.get("url")?,
+                urls: urls
+                    .and_then(|u| serde_json::from_str(&u).ok())
+                    .unwrap_or_default(),
+                sniff: row.get("sniff")?,
+                cloud_id: row.get("cloud_id")?,
+                auth_type: row.get("auth_type")?,
+                username: row.get("username")?,
                 password: decrypted_password,
-                region: row.get(6)?,
-                access_key: row.get(7)?,
+                region: row.get("region")?,
+                access_key: row.get("access_key")?,
                 secret_key: decrypted_secret_key,
+                api_key_id: row.get("api_key_id")?,
+                api_key: decrypted_api_key,
+                token_url: row.get("token_url")?,
+                client_id: row.get("client_id")?,
+                client_secret: decrypted_client_secret,
+                scope: row.get("scope")?,
+                ca_cert_path: row.get("ca_cert_path")?,
+                client_cert_path: row.get("client_cert_path")?,
+                client_key_path: row.get("client_key_path")?,
+                client_cert_password: decrypted_client_cert_password,
+                tls_fingerprint: row.get("tls_fingerprint")?,
+                accept_invalid_certs: row.get("accept_invalid_certs")?,
+                ssh_host: row.get("ssh_host")?,
+                ssh_port: row.get("ssh_port")?,
+                ssh_user: row.get("ssh_user")?,
+                ssh_key_path: row.get("ssh_key_path")?,
+                ssh_jump_hosts: ssh_jump_hosts
+                    .and_then(|j| serde_json::from_str(&j).ok())
+                    .unwrap_or_default(),
+                proxy_url: row.get("proxy_url")?,
+                proxy_username: row.get("proxy_username")?,
+                proxy_password: decrypted_proxy_password,
+                no_proxy: row.get("no_proxy")?,
+                headers: headers
+                    .and_then(|h| decrypt_headers(mc, &h).ok())
+                    .unwrap_or_default(),
+                retry: retry_policy
+                    .and_then(|r| serde_json::from_str(&r).ok())
+                    .unwrap_or_default(),
+                connect_timeout_ms: row.get("connect_timeout_ms")?,
+                request_timeout_ms: row.get("request_timeout_ms")?,
+                max_concurrent_requests: row.get("max_concurrent_requests")?,
+                max_response_bytes: row.get("max_response_bytes")?,
+                compression: compression
+                    .and_then(|c| serde_json::from_str(&c).ok())
+                    .unwrap_or_default(),
+                credential_source: row.get("credential_source")?,
+                aws_profile: row.get("aws_profile")?,
+                session_token: decrypted_session_token,
+                role_arn: row.get("role_arn")?,
+                role_session_name: row.get("role_session_name")?,
+                external_id: row.get("external_id")?,
+                sts_endpoint: row.get("sts_endpoint")?,
+                aws_service: row.get("aws_service")?,
+                unsigned_payload: row.get("unsigned_payload")?,
+                credential_helper: row.get("credential_helper")?,
+                credential_helper_ttl: row.get("credential_helper_ttl")?,
+                tags: tags
+                    .and_then(|t| serde_json::from_str(&t).ok())
+                    .unwrap_or_default(),
+                read_only: false,
+                source: None,
             })
         })?;
 
@@ -96,9 +555,176 @@ impl Database {
         Ok(profiles)
     }
 
+    /// Saves the profiles in one transaction, so a failure saves none.
+    pub fn save_profiles(&self, profiles: &[ConnectionProfile]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for profile in profiles {
+            self.save_profile(profile)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
     pub fn delete_profile(&self, id: &str) -> Result<()> {
         self.conn
             .execute("DELETE FROM profiles WHERE id = ?1", params![id])?;
         Ok(())
     }
 }
+
+// Header lists are stored as JSON with only the secret values encrypted
+fn encrypt_headers(mc: &MagicCrypt256, headers: &[CustomHeader]) -> Result<String> {
+    let stored: Vec<CustomHeader> = headers
+        .iter()
+        .map(|h| CustomHeader {
+            value: if h.secret {
+                mc.encrypt_str_to_base64(&h.value)
+            } else {
+                h.value.clone()
+            },
+            ..h.clone()
+        })
+        .collect();
+    Ok(serde_json::to_string(&stored)?)
+}
+
+fn decrypt_headers(mc: &MagicCrypt256, stored: &str) -> Result<Vec<CustomHeader>> {
+    let headers: Vec<CustomHeader> = serde_json::from_str(stored)?;
+    headers
+        .into_iter()
+        .map(|h| {
+            if !h.secret {
+                return Ok(h);
+            }
+            let value = mc
+                .decrypt_base64_to_string(&h.value)
+                .map_err(|e| anyhow!("{}", e))?;
+            Ok(CustomHeader { value, ..h })
+        })
+        .collect()
+}
+
+// Argon2id with the crate defaults, hex-encoded for use as a cipher key
+pub fn derive_key(password: &str, salt: &[u8]) -> Result<String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+    Ok(hex::encode(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ruskview-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn profile(id: &str) -> ConnectionProfile {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": id,
+            "url": "http://localhost:9200",
+            "auth_type": "basic",
+            "username": "elastic",
+            "password": "s3cret",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn locks_and_unlocks_with_master_password() {
+        let dir = temp_dir();
+        let mut db = Database::init(&dir).unwrap();
+        db.save_profile(&profile("prod")).unwrap();
+        assert!(!db.has_master_password().unwrap());
+
+        db.set_master_password(None, "hunter2").unwrap();
+        db.lock().unwrap();
+        assert!(db.is_locked());
+        assert!(db.get_profiles().is_err());
+
+        assert!(db.unlock("wrong").is_err());
+        assert!(db.is_locked());
+        db.unlock("hunter2").unwrap();
+        let profiles = db.get_profiles().unwrap();
+        assert_eq!(profiles[0].password.as_deref(), Some("s3cret"));
+
+        // A reopened store starts locked and needs the same password
+        drop(db);
+        let mut db = Database::init(&dir).unwrap();
+        assert!(db.is_locked());
+        db.unlock("hunter2").unwrap();
+        assert_eq!(db.get_profiles().unwrap().len(), 1);
+
+        // Changing the password requires the current one
+        assert!(db.set_master_password(None, "new").is_err());
+        db.set_master_password(Some("hunter2"), "new").unwrap();
+        db.lock().unwrap();
+        db.unlock("new").unwrap();
+        assert_eq!(profiles, db.get_profiles().unwrap());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn migrates_baseline_schema() {
+        let dir = temp_dir();
+        let conn = Connection::open(dir.join(DB_NAME)).unwrap();
+        conn.execute(
+            "CREATE TABLE profiles (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                url TEXT NOT NULL,
+                auth_type TEXT NOT NULL,
+                username TEXT,
+                password TEXT,
+                region TEXT,
+                access_key TEXT,
+                secret_key TEXT
+            )",
+            [],
+        )
+        .unwrap();
+        let legacy = new_magic_crypt!(LEGACY_ENCRYPTION_KEY, 256);
+        conn.execute(
+            "INSERT INTO profiles (id, name, url, auth_type, username, password)
+             VALUES ('old', 'Old', 'http://localhost:9200', 'basic', 'elastic', ?1)",
+            params![legacy.encrypt_str_to_base64("s3cret")],
+        )
+        .unwrap();
+        drop(conn);
+
+        let mut db = Database::init(&dir).unwrap();
+        let columns = db
+            .conn
+            .prepare("PRAGMA table_info(profiles)")
+            .unwrap()
+            .query_map([], |row| row.get::<_, String>(1))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        for (column, _) in PROFILE_MIGRATIONS {
+            assert!(columns.iter().any(|c| c == column), "missing {}", column);
+        }
+
+        let profiles = db.get_profiles().unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].password.as_deref(), Some("s3cret"));
+
+        // Setting a master password re-encrypts the legacy rows
+        db.set_master_password(None, "hunter2").unwrap();
+        db.lock().unwrap();
+        db.unlock("hunter2").unwrap();
+        assert_eq!(db.get_profiles().unwrap(), profiles);
+
+        // Migrating again is a no-op
+        drop(db);
+        Database::init(&dir).unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
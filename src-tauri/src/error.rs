@@ -0,0 +1,204 @@
+use crate::es::response::ResponseBody;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Longest response excerpt kept in messages and logs
+const PREVIEW_LEN: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    // Connection refused, reset, DNS, proxy
+    Transport,
+    // Connect or total request timeout expired
+    Timeout,
+    Tls,
+    Tunnel,
+    // Signing or credential failures, and 401/403 responses
+    Auth,
+    // Non-2xx response without an Elasticsearch error body
+    Http,
+    Elasticsearch,
+    Parse,
+    Database,
+    // Reading or writing local files
+    Io,
+    // Invalid profile settings or arguments
+    Config,
+    NotConnected,
+    // Aborted through `cancel_request`
+    Cancelled,
+    // Body over the profile's maximum response size
+    ResponseTooLarge,
+}
+
+/// The `error` object of an Elasticsearch error response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EsErrorDetails {
+    #[serde(rename = "type")]
+    pub error_type: String,
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub root_cause: Vec<RootCause>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootCause {
+    #[serde(rename = "type")]
+    pub error_type: String,
+    pub reason: Option<String>,
+    pub index: Option<String>,
+}
+
+/// Error returned by the ES client and every command, serialized to the UI
+/// as `{ kind, message, method, path, status, es_error }`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub status: Option<u16>,
+    // Boxed to keep the `Err` side of every `Result` small
+    pub es_error: Option<Box<EsErrorDetails>>,
+}
+
+impl AppError {
+    pub fn new(kind: ErrorKind, message: impl fmt::Display) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+            method: None,
+            path: None,
+            status: None,
+            es_error: None,
+        }
+    }
+
+    pub fn config(message: impl fmt::Display) -> Self {
+        Self::new(ErrorKind::Config, message)
+    }
+
+    pub fn database(message: impl fmt::Display) -> Self {
+        Self::new(ErrorKind::Database, message)
+    }
+
+    pub fn io(message: impl fmt::Display) -> Self {
+        Self::new(ErrorKind::Io, message)
+    }
+
+    pub fn auth(message: impl fmt::Display) -> Self {
+        Self::new(ErrorKind::Auth, message)
+    }
+
+    pub fn not_connected() -> Self {
+        Self::new(ErrorKind::NotConnected, "No active connection")
+    }
+
+    /// Classifies a failed send, keeping the whole source chain in the
+    /// message since reqwest's own text rarely names the cause.
+    pub fn transport(error: &reqwest::Error) -> Self {
+        let kind = if error.is_timeout() {
+            ErrorKind::Timeout
+        } else if is_tls_error(error) {
+            ErrorKind::Tls
+        } else {
+            ErrorKind::Transport
+        };
+
+        let mut message = error.to_string();
+        let mut source = std::error::Error::source(error);
+        while let Some(cause) = source {
+            message.push_str(": ");
+            message.push_str(&cause.to_string());
+            source = cause.source();
+        }
+        Self::new(kind, message)
+    }
+
+    pub fn from_response(status: u16, body: &ResponseBody) -> Self {
+        let es_error = match body {
+            ResponseBody::Json(value) => value
+                .get("error")
+                .and_then(|e| serde_json::from_value::<EsErrorDetails>(e.clone()).ok()),
+            _ => None,
+        };
+
+        let kind = match status {
+            401 | 403 => ErrorKind::Auth,
+            _ if es_error.is_some() => ErrorKind::Elasticsearch,
+            _ => ErrorKind::Http,
+        };
+        let message = match &es_error {
+            Some(EsErrorDetails {
+                error_type,
+                reason: Some(reason),
+                ..
+            }) => format!("{}: {}", error_type, reason),
+            Some(details) => details.error_type.clone(),
+            None => format!("HTTP {}: {}", status, preview(&body.as_text())),
+        };
+
+        Self {
+            status: Some(status),
+            es_error: es_error.map(Box::new),
+            ..Self::new(kind, message)
+        }
+    }
+
+    pub fn with_request(mut self, method: &str, path: &str) -> Self {
+        self.method = Some(method.to_string());
+        self.path = Some(path.to_string());
+        self
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.method, &self.path) {
+            (Some(method), Some(path)) => write!(f, "{} {}: {}", method, path, self.message),
+            _ => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+// A poisoned profile store lock
+impl<T> From<std::sync::PoisonError<T>> for AppError {
+    fn from(_: std::sync::PoisonError<T>) -> Self {
+        Self::database("Failed to lock db")
+    }
+}
+
+// rustls errors surface either directly or wrapped in an io::Error,
+// whose `source` skips the wrapped error
+fn is_tls_error(error: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        if cause.downcast_ref::<rustls::Error>().is_some() {
+            return true;
+        }
+        if let Some(io) = cause.downcast_ref::<std::io::Error>()
+            && io
+                .get_ref()
+                .is_some_and(|inner| inner.downcast_ref::<rustls::Error>().is_some())
+        {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
+// At most PREVIEW_LEN bytes, cut on a char boundary
+pub fn preview(text: &str) -> String {
+    if text.len() <= PREVIEW_LEN {
+        return text.to_string();
+    }
+    let mut end = PREVIEW_LEN;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &text[..end])
+}
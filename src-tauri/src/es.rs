@@ -1,3 +1,11 @@
+pub mod cancel;
 pub mod cluster;
+pub mod http_pool;
 pub mod index;
 pub mod client;
+pub mod node_pool;
+pub mod response;
+pub mod retry;
+pub mod scheduler;
+pub mod tls;
+pub mod tunnel;
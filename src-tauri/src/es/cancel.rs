@@ -0,0 +1,96 @@
+use crate::error::AppError;
+use crate::es::client::{EsClient, RequestOptions};
+use crate::es::scheduler::{Priority, RequestJob, Scheduler};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use tracing::info;
+
+// Endpoints whose work keeps running on the cluster after the client gives up
+const SEARCH_ENDPOINTS: &[&str] = &["_search", "_msearch", "_count", "_async_search"];
+
+/// A request started through `proxy_request` that can still be cancelled.
+pub struct InFlightRequest {
+    pub cancel: oneshot::Sender<()>,
+    // Client the request went through, for cancelling its server-side task
+    pub client: Arc<dyn EsClient>,
+    pub opaque_id: String,
+    pub path: String,
+}
+
+impl InFlightRequest {
+    pub fn is_search(&self) -> bool {
+        let path = self.path.split('?').next().unwrap_or_default();
+        path.split('/')
+            .any(|segment| SEARCH_ENDPOINTS.contains(&segment))
+    }
+}
+
+// Opaque ids are prefixed so our tasks are recognizable in `_tasks` and slow logs
+pub fn opaque_id(request_id: &str) -> String {
+    format!("ruskview-{}", request_id)
+}
+
+/// Cancels the cluster tasks started with the given X-Opaque-Id and
+/// returns how many were cancelled. Child tasks follow their parent.
+pub async fn cancel_tasks(
+    scheduler: &Arc<Scheduler>,
+    client: &Arc<dyn EsClient>,
+    opaque_id: &str,
+) -> Result<usize, AppError> {
+    let tasks = send(
+        scheduler,
+        client,
+        "GET",
+        "/_tasks?detailed=true&actions=indices:data/read/*",
+    )
+    .await?;
+
+    let task_ids: Vec<String> = tasks
+        .get("nodes")
+        .and_then(|n| n.as_object())
+        .into_iter()
+        .flat_map(|nodes| nodes.values())
+        .filter_map(|node| node.get("tasks")?.as_object())
+        .flat_map(|tasks| tasks.iter())
+        .filter(|(_, task)| {
+            task.pointer("/headers/X-Opaque-Id")
+                .and_then(|v| v.as_str())
+                == Some(opaque_id)
+                && task.get("parent_task_id").is_none()
+        })
+        .map(|(id, _)| id.clone())
+        .collect();
+
+    for task_id in &task_ids {
+        info!("Cancelling task {} ({})", task_id, opaque_id);
+        send(
+            scheduler,
+            client,
+            "POST",
+            &format!("/_tasks/{}/_cancel", task_id),
+        )
+        .await?;
+    }
+    Ok(task_ids.len())
+}
+
+// The user asked for the cancel, so it goes ahead of background work
+async fn send(
+    scheduler: &Arc<Scheduler>,
+    client: &Arc<dyn EsClient>,
+    method: &str,
+    path: &str,
+) -> Result<Value, AppError> {
+    scheduler
+        .submit(RequestJob {
+            client: client.clone(),
+            priority: Priority::Interactive,
+            method: method.to_string(),
+            path: path.to_string(),
+            body: None,
+            options: RequestOptions::default(),
+        })
+        .await?
+        .into_json()
+}
@@ -1,11 +1,33 @@
-use crate::auth::{aws::AwsSigV4Auth, basic::BasicAuth, Authenticator};
+use crate::auth::{self, Authenticator};
+use crate::error::{self, AppError, ErrorKind};
+use crate::es::http_pool::{HttpPool, TunnelRoute};
+use crate::es::node_pool::{self, NodePool, NodeState};
+use crate::es::response::{self, BodyChunk, EsResponse, ResponseBody};
+use crate::es::retry;
+use crate::es::tunnel::SshTunnel;
 use crate::models::connection::ConnectionProfile;
-use anyhow::Result;
 use async_trait::async_trait;
-use reqwest::Client;
+use bytes::{Bytes, BytesMut};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use hyper_util::client::legacy::connect::HttpInfo;
+use reqwest::{Client, ResponseBuilderExt};
 use serde_json::Value;
+use std::io::Write;
 use std::sync::Arc;
-use tracing::info;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+// How often the node list is refreshed when sniffing is enabled
+const SNIFF_INTERVAL: Duration = Duration::from_secs(300);
+// Used when the profile does not set its own
+const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 60_000;
+const DEFAULT_MAX_RESPONSE_BYTES: u64 = 512 * 1024 * 1024;
+// Streamed bodies are forwarded in pieces of at least this size
+const STREAM_CHUNK_SIZE: usize = 256 * 1024;
+/// Pieces a streamed body may run ahead of the UI before reading pauses.
+pub const STREAM_CHANNEL_CAPACITY: usize = 4;
 
 #[async_trait]
 pub trait EsClient: Send + Sync {
@@ -14,93 +36,606 @@ pub trait EsClient: Send + Sync {
         method: &str,
         path: &str,
         body: Option<Value>,
-    ) -> Result<Value, String>;
+        options: &RequestOptions,
+    ) -> Result<EsResponse, AppError>;
+
+    fn nodes(&self) -> Vec<NodeState>;
+
+    // The profile the client was built from, with a Cloud ID resolved
+    fn profile(&self) -> &ConnectionProfile;
+
+    // Claims the next node sniff if one is due. The caller then sends
+    // `GET /_nodes/http` and passes the response to `apply_sniff`.
+    fn sniff_due(&self) -> bool {
+        false
+    }
+
+    fn apply_sniff(&self, _response: &Value) {}
+}
+
+/// Per-call settings on top of the profile's.
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    // Sent as X-Opaque-Id so the server-side task can be found again
+    pub opaque_id: Option<String>,
+    // Overrides the profile's total request timeout; zero disables it.
+    // Streamed requests use it as an idle timeout instead.
+    pub timeout: Option<Duration>,
+    // Keep the body as received (`ResponseBody::Raw`) instead of parsing it
+    pub raw_body: bool,
+    // Stream the body here as it arrives instead of keeping it. The
+    // request fails once the receiver is dropped.
+    pub chunks: Option<mpsc::Sender<BodyChunk>>,
+}
+
+// A serialized request body, gzipped if the profile asks for it. Every
+// attempt signs these exact bytes.
+#[derive(Default)]
+struct RequestBody {
+    bytes: Vec<u8>,
+    gzip: bool,
 }
 
 pub struct StandardEsClient {
     client: Client,
+    pool: Arc<HttpPool>,
     profile: ConnectionProfile,
+    auth: Option<Box<dyn Authenticator>>,
+    nodes: NodePool,
+    sniff: bool,
+    // Time of the last sniff; None until the first request
+    last_sniff: std::sync::Mutex<Option<Instant>>,
+    // The cluster's own `host[:port]`, sent as Host through a tunnel
+    host_header: Option<String>,
+    // Kept alive for the client's lifetime; dropping it closes the forward
+    _tunnel: Option<SshTunnel>,
 }
 
 impl StandardEsClient {
-    pub fn new(profile: ConnectionProfile) -> Self {
-        let client = Client::builder()
-            .pool_idle_timeout(std::time::Duration::from_secs(90))
-            .pool_max_idle_per_host(10)
-            .build()
-            .expect("Failed to create HTTP client");
+    pub fn new(
+        mut profile: ConnectionProfile,
+        tunnel: Option<SshTunnel>,
+        pool: Arc<HttpPool>,
+    ) -> Result<Self, AppError> {
+        profile.url = profile.resolved_url().map_err(AppError::config)?;
+
+        // Send traffic into the forward while keeping the original host for
+        // TLS verification and the Host header
+        let mut host_header = None;
+        let route = match &tunnel {
+            Some(tunnel) => {
+                let mut url = reqwest::Url::parse(&profile.url).map_err(AppError::config)?;
+                let authority = url
+                    .host_str()
+                    .ok_or_else(|| AppError::config("Cluster URL has no host"))?
+                    .to_string();
+                // IPv6 addresses come bracketed
+                let host = authority
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_string();
+                host_header = Some(match url.port() {
+                    Some(port) => format!("{}:{}", authority, port),
+                    None => authority,
+                });
+                // IP literals are never looked up, so only a name can be
+                // resolved to the forward
+                if host.parse::<std::net::IpAddr>().is_ok() {
+                    url.set_host(Some("127.0.0.1")).map_err(AppError::config)?;
+                }
+                url.set_port(Some(tunnel.local_addr().port()))
+                    .map_err(|_| AppError::config("Cluster URL cannot have a port"))?;
+                profile.url = url.to_string();
+                Some(TunnelRoute {
+                    host,
+                    local_addr: tunnel.local_addr(),
+                })
+            }
+            None => None,
+        };
+        let client = pool.client(&profile, route.as_ref())?;
 
-        Self { client, profile }
+        let auth = auth::for_profile(&profile, &client);
+
+        // A tunnel only forwards to the primary URL
+        let mut seeds = vec![profile.url.clone()];
+        if tunnel.is_none() {
+            seeds.extend(profile.urls.iter().cloned());
+        }
+        let nodes = NodePool::new(seeds);
+        let sniff = profile.sniff && tunnel.is_none();
+
+        Ok(Self {
+            client,
+            pool,
+            profile,
+            auth,
+            nodes,
+            sniff,
+            last_sniff: std::sync::Mutex::new(None),
+            host_header,
+            _tunnel: tunnel,
+        })
     }
-}
 
-#[async_trait]
-impl EsClient for StandardEsClient {
-    async fn proxy_request(
+    async fn build_request(
         &self,
         method: &str,
-        path: &str,
-        body: Option<Value>,
-    ) -> Result<Value, String> {
-        let url = format!("{}{}", self.profile.url.trim_end_matches('/'), path);
-        info!("Preparing request: {} {}", method, url);
-
+        url: &str,
+        body: &RequestBody,
+        options: &RequestOptions,
+    ) -> Result<reqwest::Request, AppError> {
         // 1. Build http::Request
-        let mut builder = http::Request::builder().method(method).uri(&url);
+        let mut builder = http::Request::builder().method(method).uri(url);
 
         // Add Content-Type header if body is present
-        if body.is_some() {
+        if !body.bytes.is_empty() {
             builder = builder.header("Content-Type", "application/json");
         }
+        if body.gzip {
+            builder = builder.header("Content-Encoding", "gzip");
+        }
 
-        let body_bytes = if let Some(b) = body {
-            serde_json::to_vec(&b).map_err(|e| e.to_string())?
-        } else {
-            Vec::new()
-        };
+        // Static profile headers go in before signing so SigV4 covers them
+        for header in &self.profile.headers {
+            builder = builder.header(header.name.as_str(), header.value.as_str());
+        }
+        if let Some(opaque_id) = &options.opaque_id {
+            builder = builder.header("X-Opaque-Id", opaque_id.as_str());
+        }
+        if let Some(host) = &self.host_header {
+            builder = builder.header("Host", host.as_str());
+        }
 
-        let mut request = builder.body(body_bytes).map_err(|e| e.to_string())?;
+        let mut request = builder.body(body.bytes.clone()).map_err(AppError::config)?;
 
         // 2. Sign Request
-        if self.profile.auth_type == "iam" {
-            AwsSigV4Auth
-                .sign(&mut request, &self.profile)
-                .map_err(|e| e.to_string())?;
-        } else if self.profile.auth_type == "basic" {
-            BasicAuth
-                .sign(&mut request, &self.profile)
-                .map_err(|e| e.to_string())?;
+        if let Some(auth) = &self.auth {
+            auth.sign(&mut request, &self.profile)
+                .await
+                .map_err(|e| AppError::auth(format!("{:#}", e)))?;
         }
 
         // 3. Convert to reqwest::Request
-        let reqwest_request = reqwest::Request::try_from(request).map_err(|e| e.to_string())?;
+        let mut request = reqwest::Request::try_from(request).map_err(AppError::config)?;
+        if options.chunks.is_none() {
+            *request.timeout_mut() = self.request_timeout(options);
+        }
+        Ok(request)
+    }
+
+    // Covers one attempt from connecting to the end of the body
+    fn request_timeout(&self, options: &RequestOptions) -> Option<Duration> {
+        let timeout = options
+            .timeout
+            .or_else(|| self.profile.request_timeout_ms.map(Duration::from_millis))
+            .unwrap_or(Duration::from_millis(DEFAULT_REQUEST_TIMEOUT_MS));
+        (!timeout.is_zero()).then_some(timeout)
+    }
+
+    // A large streamed body can take far longer than any total timeout, so
+    // streams only limit how long they wait for the headers or the next piece
+    fn idle_timeout(&self, options: &RequestOptions) -> Option<Duration> {
+        options
+            .chunks
+            .as_ref()
+            .and_then(|_| self.request_timeout(options))
+    }
+
+    async fn send(
+        &self,
+        request: reqwest::Request,
+        options: &RequestOptions,
+    ) -> Result<reqwest::Result<reqwest::Response>, AppError> {
+        let sent = self.client.execute(request);
+        match self.idle_timeout(options) {
+            Some(idle) => tokio::time::timeout(idle, sent)
+                .await
+                .map_err(|_| idle_timed_out(idle)),
+            None => Ok(sent.await),
+        }
+    }
+
+    // Runs the request under the profile's retry policy. Every attempt
+    // builds and signs the request afresh, so SigV4 timestamps stay valid.
+    async fn execute(
+        &self,
+        method: &str,
+        path: &str,
+        body: &RequestBody,
+        options: &RequestOptions,
+    ) -> Result<reqwest::Response, AppError> {
+        let policy = &self.profile.retry;
+        let max_attempts = if policy.retry_non_idempotent || retry::is_idempotent(method, path) {
+            policy.max_attempts.max(1)
+        } else {
+            1
+        };
+
+        let mut attempt = 1;
+        loop {
+            let result = self.execute_once(method, path, body, options).await;
+            let delay = match &result {
+                _ if attempt >= max_attempts => None,
+                Ok(res) if policy.retry_on_status.contains(&res.status().as_u16()) => {
+                    match retry::retry_after(res.headers()) {
+                        // Too long to wait for; hand the response back
+                        Some(wait) if wait > Duration::from_millis(policy.max_backoff_ms) => None,
+                        Some(wait) => Some(wait),
+                        None => Some(retry::backoff(policy, attempt)),
+                    }
+                }
+                // TLS failures will not go away by themselves
+                Err(e)
+                    if (policy.retry_on_transport_errors && e.kind == ErrorKind::Transport)
+                        || (policy.retry_on_timeouts && e.kind == ErrorKind::Timeout) =>
+                {
+                    Some(retry::backoff(policy, attempt))
+                }
+                _ => None,
+            };
+
+            let Some(delay) = delay else {
+                return result.map_err(|e| e.with_request(method, path));
+            };
+            warn!(
+                "Attempt {}/{} of {} {} failed, retrying in {:?}",
+                attempt, max_attempts, method, path, delay
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    // Sends the request to the next live node, failing over to the others
+    // on connection errors. Nothing reached the server in that case, so
+    // failing over is safe for any method.
+    async fn execute_once(
+        &self,
+        method: &str,
+        path: &str,
+        body: &RequestBody,
+        options: &RequestOptions,
+    ) -> Result<reqwest::Response, AppError> {
+        let mut last_error = None;
+        for _ in 0..self.nodes.node_count() {
+            let Some(node) = self.nodes.next() else {
+                break;
+            };
+            let url = format!("{}{}", node, path);
+            info!("Preparing request: {} {}", method, url);
+
+            let request = self.build_request(method, &url, body, options).await?;
+            let mut res = match self.send(request, options).await? {
+                Ok(res) => res,
+                Err(e) if e.is_connect() => {
+                    warn!("Node {} unreachable: {}", node, e);
+                    self.nodes.mark_dead(&node);
+                    last_error = Some(e);
+                    continue;
+                }
+                Err(e) => return Err(AppError::transport(&e)),
+            };
+            self.nodes.mark_alive(&node);
+
+            // Expired or revoked tokens: refresh once and replay the request
+            if let Some(auth) = &self.auth
+                && auth.is_expired_status(res.status().as_u16())
+            {
+                let (buffered, rejection) = buffer_response(res).await?;
+                res = buffered;
+                if auth.invalidate(&rejection).await {
+                    info!(
+                        "Got {}, refreshing credentials and retrying",
+                        res.status().as_u16()
+                    );
+                    let request = self.build_request(method, &url, body, options).await?;
+                    res = self
+                        .send(request, options)
+                        .await?
+                        .map_err(|e| AppError::transport(&e))?;
+                }
+            }
+            return Ok(res);
+        }
+
+        Err(match last_error {
+            Some(e) => AppError::transport(&e),
+            None => AppError::config("No nodes configured"),
+        })
+    }
+
+    // Gzips the body when the profile asks for it and it is large enough
+    // to be worth it
+    fn encode_body(&self, bytes: Vec<u8>) -> Result<RequestBody, AppError> {
+        let compression = &self.profile.compression;
+        if !compression.requests || bytes.len() < compression.min_request_bytes {
+            return Ok(RequestBody { bytes, gzip: false });
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&bytes).map_err(AppError::io)?;
+        let compressed = encoder.finish().map_err(AppError::io)?;
+        debug!(
+            "Gzipped request body from {} to {} bytes",
+            bytes.len(),
+            compressed.len()
+        );
+        Ok(RequestBody {
+            bytes: compressed,
+            gzip: true,
+        })
+    }
+
+    // Reads the body up to the profile's size limit and returns it with its
+    // size. With `options.chunks` set, it is sent there in pieces instead
+    // and the returned bytes are empty.
+    async fn read_body(
+        &self,
+        mut res: reqwest::Response,
+        options: &RequestOptions,
+    ) -> Result<(Bytes, usize), AppError> {
+        let limit = self
+            .profile
+            .max_response_bytes
+            .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+        let too_large = || {
+            AppError::new(
+                ErrorKind::ResponseTooLarge,
+                format!(
+                    "Response is larger than the {:.1} MiB allowed by this profile",
+                    limit as f64 / (1024.0 * 1024.0)
+                ),
+            )
+        };
+        // None for decompressed responses, which are then only checked
+        // as they arrive
+        let expected = res.content_length();
+        if expected.is_some_and(|len| len > limit) {
+            return Err(too_large());
+        }
+
+        let mut body = BytesMut::new();
+        if options.chunks.is_none() {
+            body.reserve(expected.unwrap_or(0) as usize);
+        }
+        let idle_timeout = self.idle_timeout(options);
+        let mut received = 0u64;
+        loop {
+            let next = match idle_timeout {
+                Some(idle) => tokio::time::timeout(idle, res.chunk())
+                    .await
+                    .map_err(|_| idle_timed_out(idle))?,
+                None => res.chunk().await,
+            };
+            let Some(chunk) = next.map_err(|e| AppError::transport(&e))? else {
+                break;
+            };
+            received += chunk.len() as u64;
+            // Dropping `res` here aborts the transfer
+            if received > limit {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+
+            if let Some(chunks) = &options.chunks
+                && body.len() >= STREAM_CHUNK_SIZE
+            {
+                let data = body.split().freeze();
+                send_chunk(chunks, data, received, expected).await?;
+            }
+        }
+        if let Some(chunks) = &options.chunks
+            && !body.is_empty()
+        {
+            let data = body.split().freeze();
+            send_chunk(chunks, data, received, expected).await?;
+        }
+        Ok((body.freeze(), received as usize))
+    }
+}
+
+#[async_trait]
+impl EsClient for StandardEsClient {
+    async fn proxy_request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+        options: &RequestOptions,
+    ) -> Result<EsResponse, AppError> {
+        // When the caller sets a timeout, searches also get an ES-side one a
+        // little under it, so the cluster returns partial results before we
+        // give up on it. The profile's default alone never changes results.
+        let path = match options.timeout.filter(|t| !t.is_zero()) {
+            Some(timeout) if is_search(path) && !has_query_param(path, "timeout") => {
+                let separator = if path.contains('?') { '&' } else { '?' };
+                format!(
+                    "{}{}timeout={}ms",
+                    path,
+                    separator,
+                    (timeout * 9 / 10).as_millis()
+                )
+            }
+            _ => path.to_string(),
+        };
+        let path = path.as_str();
+
+        let body_bytes = if let Some(b) = body {
+            self.encode_body(serde_json::to_vec(&b).map_err(AppError::config)?)?
+        } else {
+            RequestBody::default()
+        };
 
         // 4. Execute
-        let res = self
-            .client
-            .execute(reqwest_request)
-            .await
-            .map_err(|e| e.to_string())?;
+        let started = Instant::now();
+        let res = self.execute(method, path, &body_bytes, options).await?;
 
         // 5. Handle Response
         let status = res.status();
         info!("Response status: {}", status);
 
-        let text = res.text().await.map_err(|e| e.to_string())?;
+        let _connection = self.pool.checkout(&res);
+        let headers = response::forwarded_headers(res.headers());
+        let content_type = res
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let (bytes, size) = self
+            .read_body(res, options)
+            .await
+            .map_err(|e| e.with_request(method, path))?;
 
         // Log preview
-        let log_text = if text.len() > 500 {
-            format!("{}...", &text[..500])
-        } else {
-            text.clone()
-        };
-        info!("Response body preview: {}", log_text);
+        info!(
+            "Response body preview: {}",
+            error::preview(&String::from_utf8_lossy(&bytes))
+        );
+
+        Ok(EsResponse {
+            status: status.as_u16(),
+            headers,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            size,
+            body: if options.raw_body {
+                ResponseBody::Raw(bytes)
+            } else {
+                ResponseBody::parse(&bytes, content_type.as_deref())
+            },
+            content_type,
+            method: method.to_string(),
+            path: path.to_string(),
+        })
+    }
 
-        serde_json::from_str(&text)
-            .map_err(|e| format!("Failed to parse JSON: {}. Body: {}", e, log_text))
+    fn nodes(&self) -> Vec<NodeState> {
+        self.nodes.state()
     }
+
+    fn profile(&self) -> &ConnectionProfile {
+        &self.profile
+    }
+
+    fn sniff_due(&self) -> bool {
+        if !self.sniff {
+            return false;
+        }
+        let mut last_sniff = self
+            .last_sniff
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if last_sniff.is_some_and(|t| t.elapsed() < SNIFF_INTERVAL) {
+            return false;
+        }
+        *last_sniff = Some(Instant::now());
+        true
+    }
+
+    fn apply_sniff(&self, response: &Value) {
+        let scheme = reqwest::Url::parse(&self.profile.url)
+            .map(|u| u.scheme().to_string())
+            .unwrap_or_else(|_| "http".to_string());
+        let urls = node_pool::sniffed_urls(response, &scheme);
+        if !urls.is_empty() {
+            info!("Sniffed {} nodes", urls.len());
+            self.nodes.set_sniffed(urls);
+        }
+    }
+}
+
+// Reads the body of a short error response and rebuilds the response
+// around it, keeping what the pool and callers look at
+async fn buffer_response(res: reqwest::Response) -> Result<(reqwest::Response, Bytes), AppError> {
+    let mut builder = http::Response::builder()
+        .status(res.status())
+        .version(res.version())
+        .url(res.url().clone());
+    if let Some(info) = res.extensions().get::<HttpInfo>() {
+        builder = builder.extension(info.clone());
+    }
+    for (name, value) in res.headers() {
+        builder = builder.header(name, value);
+    }
+    let body = res.bytes().await.map_err(|e| AppError::transport(&e))?;
+    let rebuilt = builder.body(body.clone()).map_err(AppError::config)?;
+    Ok((reqwest::Response::from(rebuilt), body))
+}
+
+fn idle_timed_out(idle: Duration) -> AppError {
+    AppError::new(
+        ErrorKind::Timeout,
+        format!("No data received for {} ms", idle.as_millis()),
+    )
 }
 
-pub fn create_client(profile: ConnectionProfile) -> Arc<dyn EsClient> {
-    Arc::new(StandardEsClient::new(profile))
+// Waits while the receiver is full. Failing once it is gone drops the
+// response, which aborts the transfer.
+async fn send_chunk(
+    chunks: &mpsc::Sender<BodyChunk>,
+    data: Bytes,
+    received: u64,
+    expected: Option<u64>,
+) -> Result<(), AppError> {
+    chunks
+        .send(BodyChunk {
+            data,
+            received,
+            expected,
+        })
+        .await
+        .map_err(|_| AppError::io("Response stream was closed"))
+}
+
+// Only the search endpoint itself takes `timeout`; `_search/scroll` and
+// others below it reject unknown parameters
+fn is_search(path: &str) -> bool {
+    let path = path.split('?').next().unwrap_or_default();
+    path.trim_end_matches('/').rsplit('/').next() == Some("_search")
+}
+
+fn has_query_param(path: &str, name: &str) -> bool {
+    path.split_once('?').is_some_and(|(_, query)| {
+        query
+            .split('&')
+            .any(|pair| pair.split('=').next() == Some(name))
+    })
+}
+
+pub fn create_client(
+    profile: ConnectionProfile,
+    tunnel: Option<SshTunnel>,
+    pool: Arc<HttpPool>,
+) -> Result<Arc<dyn EsClient>, AppError> {
+    Ok(Arc::new(StandardEsClient::new(profile, tunnel, pool)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_search_matches_search_endpoints() {
+        assert!(is_search("/_search"));
+        assert!(is_search("/logs-*/_search"));
+        assert!(is_search("/logs/_search/?size=0"));
+        assert!(is_search("/logs/_search?q=error"));
+    }
+
+    #[test]
+    fn is_search_skips_scroll_and_other_endpoints() {
+        assert!(!is_search("/_search/scroll"));
+        assert!(!is_search("/_search/scroll?scroll=1m"));
+        assert!(!is_search("/_msearch"));
+        assert!(!is_search("/_cat/indices"));
+        assert!(!is_search("/_search_shards"));
+    }
+
+    #[test]
+    fn has_query_param_matches_names_only() {
+        assert!(has_query_param("/_search?size=0&timeout=1s", "timeout"));
+        assert!(has_query_param("/_search?timeout", "timeout"));
+        assert!(!has_query_param("/_search?q=timeout", "timeout"));
+        assert!(!has_query_param("/_search", "timeout"));
+    }
 }
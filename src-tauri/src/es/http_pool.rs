@@ -0,0 +1,354 @@
+use crate::error::{AppError, ErrorKind};
+use crate::es::tls;
+use crate::models::connection::ConnectionProfile;
+use hyper_util::client::legacy::connect::HttpInfo;
+use reqwest::{Client, Proxy};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+// Used when the profile does not set its own
+const DEFAULT_CONNECT_TIMEOUT_MS: u64 = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    pub idle_timeout_secs: u64,
+    pub max_idle_per_host: usize,
+    // Offer HTTP/2 through ALPN; servers without it stay on HTTP/1.1
+    pub http2: bool,
+    // 0 disables TCP keepalive probes
+    pub tcp_keepalive_secs: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout_secs: 90,
+            max_idle_per_host: 10,
+            http2: true,
+            tcp_keepalive_secs: 60,
+        }
+    }
+}
+
+/// Where a tunnelled profile's connections go: the tunnel's local end.
+/// A cluster host name is resolved there; either way, the certificate is
+/// verified against the cluster's host rather than the local address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TunnelRoute {
+    // Host name or IP address from the cluster URL
+    pub host: String,
+    pub local_addr: SocketAddr,
+}
+
+// Settings reqwest fixes per client. Profiles that agree on all of them
+// share one client, and with it connections and TLS sessions. Passwords
+// are kept as digests so the key itself holds no secrets.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ClientKey {
+    connect_timeout_ms: u64,
+    tunnel: Option<TunnelRoute>,
+    proxy: Option<[Option<String>; 4]>,
+    tls: [Option<String>; 5],
+    accept_invalid_certs: bool,
+    decompress: bool,
+}
+
+impl ClientKey {
+    fn new(profile: &ConnectionProfile, tunnel: Option<&TunnelRoute>) -> Self {
+        // Tunnelled traffic never goes through the proxy
+        let proxy = tunnel.is_none().then(|| {
+            [
+                profile.proxy_url.clone(),
+                profile.proxy_username.clone(),
+                secret_digest(profile.proxy_password.as_deref()),
+                profile.no_proxy.clone(),
+            ]
+        });
+        Self {
+            connect_timeout_ms: profile
+                .connect_timeout_ms
+                .unwrap_or(DEFAULT_CONNECT_TIMEOUT_MS),
+            tunnel: tunnel.cloned(),
+            proxy,
+            tls: [
+                profile.ca_cert_path.clone(),
+                profile.client_cert_path.clone(),
+                profile.client_key_path.clone(),
+                secret_digest(profile.client_cert_password.as_deref()),
+                profile.tls_fingerprint.clone(),
+            ],
+            accept_invalid_certs: profile.accept_invalid_certs,
+            decompress: profile.compression.responses,
+        }
+    }
+}
+
+fn secret_digest(secret: Option<&str>) -> Option<String> {
+    secret.map(|s| hex::encode(Sha256::digest(s.as_bytes())))
+}
+
+struct Connection {
+    in_use: usize,
+    last_used: Instant,
+    http2: bool,
+}
+
+#[derive(Default)]
+struct HostConnections {
+    // One entry per TCP connection, by local socket address
+    connections: HashMap<SocketAddr, Connection>,
+    opened: u64,
+    requests: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    config: PoolConfig,
+    clients: HashMap<ClientKey, Client>,
+    hosts: HashMap<String, HostConnections>,
+}
+
+impl Inner {
+    // Forgets connections hyper will have closed for being idle too long
+    fn prune(&mut self) {
+        let idle_timeout = Duration::from_secs(self.config.idle_timeout_secs);
+        for host in self.hosts.values_mut() {
+            host.connections
+                .retain(|_, c| c.in_use > 0 || c.last_used.elapsed() < idle_timeout);
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HostStats {
+    // Origin as `scheme://host:port`
+    pub host: String,
+    // Connection counts are inferred from response local addresses, see
+    // `HttpPool`, and named so the UI does not show them as exact
+    pub estimated_open: usize,
+    pub estimated_idle: usize,
+    pub estimated_in_use: usize,
+    pub http2: bool,
+    // Connections opened and requests answered since startup
+    pub estimated_opened: u64,
+    pub requests: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PoolStats {
+    pub config: PoolConfig,
+    pub clients: usize,
+    pub hosts: Vec<HostStats>,
+}
+
+/// The HTTP clients shared by every `StandardEsClient`.
+///
+/// Connections are told apart by the local address reqwest reports with
+/// each response. A connection the server closes early is only forgotten
+/// once the idle timeout passes.
+pub struct HttpPool {
+    inner: Mutex<Inner>,
+}
+
+impl HttpPool {
+    pub fn new(config: PoolConfig) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                config,
+                ..Inner::default()
+            }),
+        }
+    }
+
+    /// A client for the profile's transport settings, built on first use.
+    pub fn client(
+        &self,
+        profile: &ConnectionProfile,
+        tunnel: Option<&TunnelRoute>,
+    ) -> Result<Client, AppError> {
+        let key = ClientKey::new(profile, tunnel);
+        let mut inner = self.lock();
+        if let Some(client) = inner.clients.get(&key) {
+            return Ok(client.clone());
+        }
+        // Every tunnel has its own local port, so its client is never shared
+        if key.tunnel.is_some() {
+            return build_client(&inner.config, &key, profile);
+        }
+
+        let client = build_client(&inner.config, &key, profile)?;
+        inner.clients.insert(key, client.clone());
+        info!("HTTP pool now holds {} clients", inner.clients.len());
+        Ok(client)
+    }
+
+    /// Applies new settings to clients built from now on. Existing
+    /// connections keep theirs until they reconnect.
+    pub fn set_config(&self, config: PoolConfig) {
+        let mut inner = self.lock();
+        inner.config = config;
+        inner.clients.clear();
+    }
+
+    /// Drops every client, along with the TLS configs holding decrypted
+    /// client keys. Sessions still using one keep it until they close.
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.clients.clear();
+        inner.hosts.clear();
+    }
+
+    /// Records the connection a response arrived on. It counts as in use
+    /// until the returned lease is dropped, once the body has been read.
+    pub fn checkout(self: &Arc<Self>, res: &reqwest::Response) -> Option<ConnectionLease> {
+        let local_addr = res.extensions().get::<HttpInfo>()?.local_addr();
+        let host = res.url().origin().ascii_serialization();
+        let http2 = res.version() == reqwest::Version::HTTP_2;
+
+        let mut inner = self.lock();
+        inner.prune();
+        let entry = inner.hosts.entry(host.clone()).or_default();
+        entry.requests += 1;
+        if !entry.connections.contains_key(&local_addr) {
+            entry.opened += 1;
+        }
+        let connection = entry.connections.entry(local_addr).or_insert(Connection {
+            in_use: 0,
+            last_used: Instant::now(),
+            http2,
+        });
+        connection.in_use += 1;
+
+        Some(ConnectionLease {
+            pool: self.clone(),
+            host,
+            local_addr,
+        })
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let mut inner = self.lock();
+        inner.prune();
+        let mut hosts: Vec<HostStats> = inner
+            .hosts
+            .iter()
+            .map(|(host, entry)| {
+                let open = entry.connections.len();
+                let in_use = entry.connections.values().filter(|c| c.in_use > 0).count();
+                HostStats {
+                    host: host.clone(),
+                    estimated_open: open,
+                    estimated_idle: open - in_use,
+                    estimated_in_use: in_use,
+                    http2: entry.connections.values().any(|c| c.http2),
+                    estimated_opened: entry.opened,
+                    requests: entry.requests,
+                }
+            })
+            .collect();
+        hosts.sort_by(|a, b| a.host.cmp(&b.host));
+
+        PoolStats {
+            config: inner.config.clone(),
+            clients: inner.clients.len(),
+            hosts,
+        }
+    }
+
+    fn release(&self, host: &str, local_addr: SocketAddr) {
+        let mut inner = self.lock();
+        if let Some(connection) = inner
+            .hosts
+            .get_mut(host)
+            .and_then(|h| h.connections.get_mut(&local_addr))
+        {
+            connection.in_use = connection.in_use.saturating_sub(1);
+            connection.last_used = Instant::now();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Marks a connection as in use while a response body is being read.
+pub struct ConnectionLease {
+    pool: Arc<HttpPool>,
+    host: String,
+    local_addr: SocketAddr,
+}
+
+impl Drop for ConnectionLease {
+    fn drop(&mut self) {
+        self.pool.release(&self.host, self.local_addr);
+    }
+}
+
+fn build_client(
+    config: &PoolConfig,
+    key: &ClientKey,
+    profile: &ConnectionProfile,
+) -> Result<Client, AppError> {
+    let mut builder = Client::builder()
+        .pool_idle_timeout(Duration::from_secs(config.idle_timeout_secs))
+        .pool_max_idle_per_host(config.max_idle_per_host)
+        .connect_timeout(Duration::from_millis(key.connect_timeout_ms))
+        .tcp_keepalive(
+            (config.tcp_keepalive_secs > 0).then(|| Duration::from_secs(config.tcp_keepalive_secs)),
+        );
+    if !config.http2 {
+        builder = builder.http1_only();
+    }
+    // Adds Accept-Encoding after signing; SigV4 does not cover it
+    builder = builder.gzip(key.decompress).deflate(key.decompress);
+
+    match &key.tunnel {
+        // IP literals are rewritten to the local address by the ES client
+        Some(route) if route.host.parse::<IpAddr>().is_ok() => {}
+        Some(route) => builder = builder.resolve(&route.host, route.local_addr),
+        None => {
+            if let Some(proxy) = build_proxy(profile)? {
+                builder = builder.proxy(proxy);
+            }
+        }
+    }
+
+    // rustls resumes sessions per client config, so reconnects after the
+    // idle timeout skip the full handshake
+    let server_name = key.tunnel.as_ref().map(|route| route.host.as_str());
+    let mut tls_config = tls::build_tls_config(profile, server_name)
+        .map_err(|e| AppError::new(ErrorKind::Tls, format!("{:#}", e)))?;
+    tls_config.alpn_protocols = if config.http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+
+    builder
+        .use_preconfigured_tls(tls_config)
+        .build()
+        .map_err(|e| AppError::config(format!("Failed to create HTTP client: {}", e)))
+}
+
+fn build_proxy(profile: &ConnectionProfile) -> Result<Option<Proxy>, AppError> {
+    let Some(url) = profile.proxy_url.as_deref().filter(|u| !u.is_empty()) else {
+        return Ok(None);
+    };
+
+    let mut proxy =
+        Proxy::all(url).map_err(|e| AppError::config(format!("Invalid proxy URL: {}", e)))?;
+    if let Some(username) = profile.proxy_username.as_deref().filter(|u| !u.is_empty()) {
+        proxy = proxy.basic_auth(username, profile.proxy_password.as_deref().unwrap_or(""));
+    }
+    if let Some(no_proxy) = profile.no_proxy.as_deref() {
+        proxy = proxy.no_proxy(reqwest::NoProxy::from_string(no_proxy));
+    }
+    Ok(Some(proxy))
+}
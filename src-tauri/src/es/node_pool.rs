@@ -0,0 +1,212 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+// Dead nodes are retried after 1s, 2s, 4s, ... up to this
+const MAX_DEAD_BACKOFF: Duration = Duration::from_secs(30);
+
+struct Node {
+    url: String,
+    // Seed URLs from the profile are never dropped by sniffing
+    seed: bool,
+    failures: u32,
+    dead_until: Option<Instant>,
+}
+
+impl Node {
+    fn new(url: String, seed: bool) -> Self {
+        Self {
+            url,
+            seed,
+            failures: 0,
+            dead_until: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NodeState {
+    pub url: String,
+    pub seed: bool,
+    pub alive: bool,
+    pub failures: u32,
+    // Time until a dead node is tried again
+    pub retry_in_ms: Option<u64>,
+}
+
+/// Round-robin over the nodes of a cluster, skipping dead ones until
+/// their backoff expires.
+pub struct NodePool {
+    nodes: Mutex<Vec<Node>>,
+    cursor: AtomicUsize,
+}
+
+impl NodePool {
+    pub fn new(seeds: Vec<String>) -> Self {
+        let mut nodes: Vec<Node> = Vec::new();
+        for url in seeds {
+            let url = url.trim_end_matches('/').to_string();
+            if !url.is_empty() && !nodes.iter().any(|n| n.url == url) {
+                nodes.push(Node::new(url, true));
+            }
+        }
+        Self {
+            nodes: Mutex::new(nodes),
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Picks the next live node. When every node is dead the one closest
+    /// to its retry time is returned, so requests never fail without trying.
+    pub fn next(&self) -> Option<String> {
+        let nodes = self.lock();
+        let now = Instant::now();
+        let alive: Vec<&Node> = nodes
+            .iter()
+            .filter(|n| n.dead_until.is_none_or(|t| t <= now))
+            .collect();
+
+        if alive.is_empty() {
+            return nodes
+                .iter()
+                .min_by_key(|n| n.dead_until)
+                .map(|n| n.url.clone());
+        }
+        let index = self.cursor.fetch_add(1, Ordering::Relaxed) % alive.len();
+        Some(alive[index].url.clone())
+    }
+
+    pub fn mark_dead(&self, url: &str) {
+        let mut nodes = self.lock();
+        if let Some(node) = nodes.iter_mut().find(|n| n.url == url) {
+            node.failures += 1;
+            let backoff = Duration::from_secs(1u64 << (node.failures - 1).min(5));
+            node.dead_until = Some(Instant::now() + backoff.min(MAX_DEAD_BACKOFF));
+        }
+    }
+
+    pub fn mark_alive(&self, url: &str) {
+        let mut nodes = self.lock();
+        if let Some(node) = nodes.iter_mut().find(|n| n.url == url) {
+            node.failures = 0;
+            node.dead_until = None;
+        }
+    }
+
+    /// Replaces the sniffed nodes, keeping seeds and the state of nodes
+    /// that are still part of the cluster.
+    pub fn set_sniffed(&self, urls: Vec<String>) {
+        let mut nodes = self.lock();
+        let mut previous = std::mem::take(&mut *nodes);
+
+        for url in urls {
+            if nodes.iter().any(|n| n.url == url) {
+                continue;
+            }
+            match previous.iter().position(|n| n.url == url) {
+                Some(index) => nodes.push(previous.swap_remove(index)),
+                None => nodes.push(Node::new(url, false)),
+            }
+        }
+        // Seeds the cluster did not report stay as a fallback
+        nodes.extend(previous.into_iter().filter(|n| n.seed));
+    }
+
+    pub fn state(&self) -> Vec<NodeState> {
+        let now = Instant::now();
+        self.lock()
+            .iter()
+            .map(|n| {
+                let retry_in = n.dead_until.filter(|t| *t > now).map(|t| t - now);
+                NodeState {
+                    url: n.url.clone(),
+                    seed: n.seed,
+                    alive: retry_in.is_none(),
+                    failures: n.failures,
+                    retry_in_ms: retry_in.map(|d| d.as_millis() as u64),
+                }
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Node>> {
+        self.nodes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Node URLs from a `_nodes/http` response. `publish_address` is either
+// `ip:port` or `hostname/ip:port`; the hostname is preferred so TLS
+// verification keeps working.
+pub fn sniffed_urls(response: &Value, scheme: &str) -> Vec<String> {
+    let Some(nodes) = response.get("nodes").and_then(|n| n.as_object()) else {
+        return Vec::new();
+    };
+
+    nodes
+        .values()
+        .filter_map(|node| node.pointer("/http/publish_address")?.as_str())
+        .filter_map(|address| {
+            let (host, ip_port) = match address.split_once('/') {
+                Some((host, ip_port)) => (Some(host), ip_port),
+                None => (None, address),
+            };
+            let (ip, port) = ip_port.rsplit_once(':')?;
+            let host = host.filter(|h| !h.is_empty()).unwrap_or(ip);
+            Some(format!("{}://{}:{}", scheme, host, port))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn nodes(addresses: &[&str]) -> Value {
+        let nodes: serde_json::Map<String, Value> = addresses
+            .iter()
+            .enumerate()
+            .map(|(i, address)| {
+                (
+                    format!("node-{}", i),
+                    json!({ "http": { "publish_address": address } }),
+                )
+            })
+            .collect();
+        json!({ "nodes": nodes })
+    }
+
+    #[test]
+    fn prefers_hostname_over_ip() {
+        let urls = sniffed_urls(&nodes(&["es1.internal/10.0.0.1:9200"]), "https");
+        assert_eq!(urls, vec!["https://es1.internal:9200"]);
+    }
+
+    #[test]
+    fn falls_back_to_ip() {
+        let urls = sniffed_urls(&nodes(&["10.0.0.1:9200", "/10.0.0.2:9201"]), "http");
+        assert_eq!(urls, vec!["http://10.0.0.1:9200", "http://10.0.0.2:9201"]);
+    }
+
+    #[test]
+    fn skips_nodes_without_http_address() {
+        let response = json!({
+            "nodes": {
+                "a": { "http": { "publish_address": "10.0.0.1:9200" } },
+                "b": { "roles": ["master"] },
+                "c": { "http": { "publish_address": "no-port" } },
+            }
+        });
+        assert_eq!(
+            sniffed_urls(&response, "http"),
+            vec!["http://10.0.0.1:9200"]
+        );
+        assert!(sniffed_urls(&json!({}), "http").is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+use crate::error::{self, AppError, ErrorKind};
+use bytes::Bytes;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// Response headers passed on to the UI
+const FORWARDED_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "content-encoding",
+    "location",
+    "retry-after",
+    "warning",
+    "x-elastic-product",
+    "x-opaque-id",
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum ResponseBody {
+    Empty,
+    Json(Value),
+    // One value per line, e.g. `_msearch` or `_bulk` style output
+    Ndjson(Vec<Value>),
+    // `_cat` APIs, plain-text errors from proxies and anything unparsable
+    Text(String),
+    // Left unparsed for `proxy_request_raw`, which sends the bytes on as-is
+    #[serde(skip)]
+    Raw(Bytes),
+}
+
+impl ResponseBody {
+    /// Parses by content type, falling back to text rather than failing.
+    pub fn parse(bytes: &[u8], content_type: Option<&str>) -> Self {
+        if bytes.is_empty() {
+            return ResponseBody::Empty;
+        }
+        let text = String::from_utf8_lossy(bytes);
+        let content_type = content_type.unwrap_or_default();
+
+        if content_type.contains("ndjson") {
+            let lines: Result<Vec<Value>, _> = text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect();
+            if let Ok(lines) = lines {
+                return ResponseBody::Ndjson(lines);
+            }
+        } else if (content_type.contains("json") || content_type.is_empty())
+            && let Ok(value) = serde_json::from_slice(bytes)
+        {
+            return ResponseBody::Json(value);
+        }
+        ResponseBody::Text(text.into_owned())
+    }
+
+    pub fn as_text(&self) -> String {
+        match self {
+            ResponseBody::Empty => String::new(),
+            ResponseBody::Json(value) => value.to_string(),
+            ResponseBody::Ndjson(lines) => lines
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ResponseBody::Text(text) => text.clone(),
+            ResponseBody::Raw(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        }
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        match self {
+            ResponseBody::Raw(bytes) => bytes,
+            body => Bytes::from(body.as_text()),
+        }
+    }
+}
+
+/// A piece of a streamed body, sent as it arrives.
+#[derive(Debug)]
+pub struct BodyChunk {
+    pub data: Bytes,
+    // Bytes received so far, and the total if the server sent Content-Length.
+    // reqwest drops Content-Length from responses it decompresses, so
+    // compressed responses have no total.
+    pub received: u64,
+    pub expected: Option<u64>,
+}
+
+/// Everything the cluster answered, whatever the status.
+#[derive(Debug, Clone, Serialize)]
+pub struct EsResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    // From sending the first attempt to the end of the body
+    pub elapsed_ms: u64,
+    // Body size in bytes, after decompression
+    pub size: usize,
+    pub content_type: Option<String>,
+    pub body: ResponseBody,
+    // Set so errors raised from the envelope name the request
+    #[serde(skip)]
+    pub method: String,
+    #[serde(skip)]
+    pub path: String,
+}
+
+impl EsResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// The JSON body of a successful response; anything else becomes an
+    /// error, for callers that only work with JSON.
+    pub fn into_json(mut self) -> Result<Value, AppError> {
+        if let ResponseBody::Raw(bytes) = &self.body {
+            self.body = ResponseBody::parse(bytes, self.content_type.as_deref());
+        }
+        if !self.is_success() {
+            return Err(AppError::from_response(self.status, &self.body)
+                .with_request(&self.method, &self.path));
+        }
+        match self.body {
+            ResponseBody::Json(value) => Ok(value),
+            ResponseBody::Empty => Ok(Value::Null),
+            body => Err(AppError {
+                status: Some(self.status),
+                ..AppError::new(
+                    ErrorKind::Parse,
+                    format!(
+                        "Expected a JSON response, got {}. Body: {}",
+                        self.content_type.as_deref().unwrap_or("no content type"),
+                        error::preview(&body.as_text())
+                    ),
+                )
+            }
+            .with_request(&self.method, &self.path)),
+        }
+    }
+
+    /// The binary IPC payload of `proxy_request_raw`: the envelope (with an
+    /// empty `body`) as one line of JSON, a newline, then the body bytes as
+    /// received. `validate` checks that a JSON or NDJSON body is well-formed.
+    pub fn into_raw_payload(mut self, validate: bool) -> Result<Vec<u8>, AppError> {
+        let bytes = std::mem::replace(&mut self.body, ResponseBody::Empty).into_bytes();
+
+        let is_json = self
+            .content_type
+            .as_deref()
+            .is_some_and(|ct| ct.contains("json"));
+        if validate && is_json {
+            let invalid = serde_json::Deserializer::from_slice(&bytes)
+                .into_iter::<serde::de::IgnoredAny>()
+                .find_map(Result::err);
+            if let Some(e) = invalid {
+                return Err(AppError {
+                    status: Some(self.status),
+                    ..AppError::new(ErrorKind::Parse, format!("Invalid JSON in response: {}", e))
+                }
+                .with_request(&self.method, &self.path));
+            }
+        }
+
+        let mut payload = serde_json::to_vec(&self).map_err(AppError::config)?;
+        payload.reserve(bytes.len() + 1);
+        payload.push(b'\n');
+        payload.extend_from_slice(&bytes);
+        Ok(payload)
+    }
+}
+
+pub fn forwarded_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    FORWARDED_HEADERS
+        .iter()
+        .filter_map(|name| {
+            let values: Vec<&str> = headers
+                .get_all(*name)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .collect();
+            (!values.is_empty()).then(|| (name.to_string(), values.join(", ")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_json() {
+        let body = ResponseBody::parse(br#"{"acknowledged":true}"#, Some("application/json"));
+        assert!(matches!(body, ResponseBody::Json(v) if v == json!({ "acknowledged": true })));
+        // Without a content type, JSON is still tried first
+        let body = ResponseBody::parse(b"[1,2]", None);
+        assert!(matches!(body, ResponseBody::Json(v) if v == json!([1, 2])));
+    }
+
+    #[test]
+    fn parses_ndjson_lines() {
+        let body = ResponseBody::parse(b"{\"a\":1}\n\n{\"b\":2}\n", Some("application/x-ndjson"));
+        assert!(
+            matches!(body, ResponseBody::Ndjson(lines) if lines == vec![json!({ "a": 1 }), json!({ "b": 2 })])
+        );
+    }
+
+    #[test]
+    fn falls_back_to_text() {
+        let body = ResponseBody::parse(b"green open logs", Some("text/plain"));
+        assert!(matches!(body, ResponseBody::Text(t) if t == "green open logs"));
+        let body = ResponseBody::parse(b"<html>Bad Gateway</html>", Some("application/json"));
+        assert!(matches!(body, ResponseBody::Text(t) if t == "<html>Bad Gateway</html>"));
+        let body = ResponseBody::parse(b"{\"a\":1}\nnot json", Some("application/x-ndjson"));
+        assert!(matches!(body, ResponseBody::Text(_)));
+    }
+
+    #[test]
+    fn empty_body() {
+        assert!(matches!(
+            ResponseBody::parse(b"", Some("application/json")),
+            ResponseBody::Empty
+        ));
+    }
+}
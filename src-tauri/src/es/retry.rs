@@ -0,0 +1,180 @@
+use crate::models::connection::RetryPolicy;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::time::Duration;
+
+// POST endpoints that only read, so replaying them is as safe as a GET.
+// Index names cannot start with an underscore, so these never match an
+// index. `_async_search` is left out since every submit starts a new task.
+const READ_ONLY_POST_ENDPOINTS: &[&str] = &[
+    "_search",
+    "_msearch",
+    "_count",
+    "_mget",
+    "_field_caps",
+    "_validate",
+    "_explain",
+    "_terms_enum",
+];
+
+// Document writes, which may add a document or a version when replayed,
+// e.g. auto-ID index actions in `_bulk`
+const DOCUMENT_WRITE_ENDPOINTS: &[&str] = &["_bulk", "_doc", "_create", "_update"];
+
+/// Whether a request can be replayed without side effects.
+pub fn is_idempotent(method: &str, path: &str) -> bool {
+    let path = path.split('?').next().unwrap_or_default();
+    let has_segment = |names: &[&str]| path.split('/').any(|segment| names.contains(&segment));
+    // Each scroll continuation moves the cursor on, so a replay loses a page
+    let is_scroll = path
+        .split('/')
+        .collect::<Vec<_>>()
+        .windows(2)
+        .any(|pair| pair == ["_search", "scroll"]);
+    match method.to_ascii_uppercase().as_str() {
+        "GET" | "POST" if is_scroll => false,
+        "GET" | "HEAD" | "OPTIONS" => true,
+        "PUT" | "DELETE" => !has_segment(DOCUMENT_WRITE_ENDPOINTS),
+        "POST" => has_segment(READ_ONLY_POST_ENDPOINTS) && !has_segment(DOCUMENT_WRITE_ENDPOINTS),
+        _ => false,
+    }
+}
+
+/// Exponential backoff with equal jitter: half of the delay is fixed,
+/// the other half random. `attempt` starts at 1.
+pub fn backoff(policy: &RetryPolicy, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    let base = policy
+        .initial_backoff_ms
+        .saturating_mul(1u64 << exponent)
+        .min(policy.max_backoff_ms);
+    let jitter = (base as f64 / 2.0 * rand::random::<f64>()) as u64;
+    Duration::from_millis(base / 2 + jitter)
+}
+
+/// Parses `Retry-After` as delay seconds or an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn reads_are_idempotent() {
+        assert!(is_idempotent("GET", "/logs/_doc/1"));
+        assert!(is_idempotent("head", "/logs"));
+        assert!(is_idempotent("POST", "/logs/_search?size=0"));
+        assert!(is_idempotent("POST", "/logs/_validate/query"));
+        assert!(is_idempotent("POST", "/_mget"));
+    }
+
+    #[test]
+    fn index_and_settings_changes_are_idempotent() {
+        assert!(is_idempotent("PUT", "/logs"));
+        assert!(is_idempotent("PUT", "/logs/_settings"));
+        assert!(is_idempotent("DELETE", "/logs"));
+        assert!(is_idempotent("DELETE", "/_search/scroll"));
+    }
+
+    #[test]
+    fn document_writes_are_not_idempotent() {
+        assert!(!is_idempotent("PUT", "/_bulk"));
+        assert!(!is_idempotent("POST", "/_bulk"));
+        assert!(!is_idempotent("PUT", "/logs/_doc/1"));
+        assert!(!is_idempotent("PUT", "/logs/_create/1"));
+        assert!(!is_idempotent("DELETE", "/logs/_doc/1"));
+        assert!(!is_idempotent("POST", "/logs/_update/1"));
+        assert!(!is_idempotent("POST", "/logs/_doc"));
+    }
+
+    #[test]
+    fn cursor_and_task_requests_are_not_idempotent() {
+        assert!(!is_idempotent("POST", "/_search/scroll"));
+        assert!(!is_idempotent("GET", "/_search/scroll?scroll=1m"));
+        assert!(!is_idempotent("POST", "/logs/_async_search"));
+        assert!(is_idempotent(
+            "GET",
+            "/_async_search/FmRldE8zREVEUzA2ZVpUeGs2ejJFUFEaMkZ5QTVrSTZSaVN3WlNFVmtlWHJsdzoxMDc="
+        ));
+    }
+
+    #[test]
+    fn scroll_index_is_not_read_only() {
+        assert!(!is_idempotent("POST", "/scroll/_doc"));
+        assert!(!is_idempotent("POST", "/scroll"));
+        assert!(!is_idempotent("PATCH", "/logs/_search"));
+    }
+
+    #[test]
+    fn backoff_grows_and_caps_with_jitter() {
+        let policy = RetryPolicy {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            ..RetryPolicy::default()
+        };
+        for (attempt, base) in [
+            (1, 100),
+            (2, 200),
+            (3, 400),
+            (4, 800),
+            (5, 1_000),
+            (40, 1_000),
+        ] {
+            let delay = backoff(&policy, attempt).as_millis() as u64;
+            assert!(
+                (base / 2..=base).contains(&delay),
+                "attempt {}: {} ms not in {}..={}",
+                attempt,
+                delay,
+                base / 2,
+                base
+            );
+        }
+        // Attempt 0 is treated like the first
+        assert!(backoff(&policy, 0) <= Duration::from_millis(100));
+    }
+
+    fn retry_after_header(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn retry_after_reads_seconds() {
+        assert_eq!(
+            retry_after(&retry_after_header("120")),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            retry_after(&retry_after_header(" 0 ")),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn retry_after_reads_http_dates() {
+        let at = chrono::Utc::now() + chrono::Duration::seconds(120);
+        let delay = retry_after(&retry_after_header(&at.to_rfc2822())).unwrap();
+        assert!(delay > Duration::from_secs(110) && delay <= Duration::from_secs(120));
+
+        let past = chrono::Utc::now() - chrono::Duration::seconds(120);
+        assert_eq!(retry_after(&retry_after_header(&past.to_rfc2822())), None);
+    }
+
+    #[test]
+    fn retry_after_ignores_missing_and_invalid_values() {
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+        assert_eq!(retry_after(&retry_after_header("soon")), None);
+        assert_eq!(retry_after(&retry_after_header("-5")), None);
+    }
+}
@@ -0,0 +1,537 @@
+use crate::error::{AppError, ErrorKind};
+use crate::es::client::{EsClient, RequestOptions};
+use crate::es::response::EsResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+
+// Used when the profile does not set its own limit
+const DEFAULT_MAX_CONCURRENT: usize = 4;
+// Background requests never hold more than this many of a cluster's slots,
+// so polling can neither crowd out the user nor pile up on a slow cluster
+const MAX_BACKGROUND_PER_CLUSTER: usize = 1;
+// Requests in flight across all clusters
+const MAX_CONCURRENT_TOTAL: usize = 16;
+// Samples kept per cluster for the wait and latency percentiles
+const SAMPLE_SIZE: usize = 200;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    // Something the user is waiting for
+    #[default]
+    Interactive,
+    // Polling and refreshes
+    Background,
+}
+
+/// A request to run through the scheduler. The cluster and its limit come
+/// from the client's profile.
+pub struct RequestJob {
+    pub client: Arc<dyn EsClient>,
+    pub priority: Priority,
+    pub method: String,
+    pub path: String,
+    pub body: Option<Value>,
+    pub options: RequestOptions,
+}
+
+struct QueuedJob {
+    job: RequestJob,
+    enqueued: Instant,
+    responder: oneshot::Sender<Result<EsResponse, AppError>>,
+}
+
+#[derive(Default)]
+struct ClusterQueue {
+    interactive: VecDeque<QueuedJob>,
+    background: VecDeque<QueuedJob>,
+    max_concurrent: usize,
+    running: usize,
+    running_background: usize,
+    completed: u64,
+    failed: u64,
+    cancelled: u64,
+    // Most recent samples, oldest first
+    waits: VecDeque<Duration>,
+    latencies: VecDeque<Duration>,
+}
+
+impl ClusterQueue {
+    fn queued(&self) -> usize {
+        self.interactive.len() + self.background.len()
+    }
+
+    // Interactive jobs go first; background jobs only get their own share
+    fn take_next(&mut self) -> Option<QueuedJob> {
+        // Callers that went away (cancelled or dropped) no longer need a slot
+        let before = self.queued();
+        self.interactive.retain(|q| !q.responder.is_closed());
+        self.background.retain(|q| !q.responder.is_closed());
+        self.cancelled += (before - self.queued()) as u64;
+
+        if self.running >= self.max_concurrent {
+            return None;
+        }
+        let next = match self.interactive.pop_front() {
+            Some(next) => next,
+            None if self.running_background < MAX_BACKGROUND_PER_CLUSTER => {
+                let next = self.background.pop_front()?;
+                self.running_background += 1;
+                next
+            }
+            None => return None,
+        };
+        self.running += 1;
+        Some(next)
+    }
+}
+
+#[derive(Default)]
+struct Queues {
+    clusters: HashMap<String, ClusterQueue>,
+    // Clusters with queued jobs, served round-robin
+    rotation: VecDeque<String>,
+    running: usize,
+}
+
+impl Queues {
+    // Takes one job per cluster per round until every cluster is at its
+    // limit or the global limit is reached, so a busy cluster cannot delay
+    // the others
+    fn take_ready(&mut self) -> Vec<(String, QueuedJob)> {
+        let mut ready = Vec::new();
+        loop {
+            let mut progressed = false;
+            for _ in 0..self.rotation.len() {
+                if self.running >= MAX_CONCURRENT_TOTAL {
+                    break;
+                }
+                let Some(cluster) = self.rotation.pop_front() else {
+                    break;
+                };
+                let Some(queue) = self.clusters.get_mut(&cluster) else {
+                    continue;
+                };
+                let next = queue.take_next();
+                let still_queued = queue.queued() > 0;
+                if let Some(next) = next {
+                    self.running += 1;
+                    ready.push((cluster.clone(), next));
+                    progressed = true;
+                }
+                if still_queued {
+                    self.rotation.push_back(cluster);
+                }
+            }
+            if !progressed || self.running >= MAX_CONCURRENT_TOTAL {
+                return ready;
+            }
+        }
+    }
+}
+
+/// Queue depth and timings of one cluster, for the UI.
+#[derive(Debug, Serialize)]
+pub struct ClusterMetrics {
+    pub cluster: String,
+    pub max_concurrent: usize,
+    pub running: usize,
+    pub queued_interactive: usize,
+    pub queued_background: usize,
+    pub completed: u64,
+    pub failed: u64,
+    pub cancelled: u64,
+    // Time spent queued before being sent
+    pub wait_p50_ms: Option<u64>,
+    pub wait_p95_ms: Option<u64>,
+    pub latency_p50_ms: Option<u64>,
+    pub latency_p95_ms: Option<u64>,
+}
+
+/// Runs every cluster request, limiting how many are in flight per cluster
+/// and overall and serving interactive requests before background ones.
+#[derive(Default)]
+pub struct Scheduler {
+    queues: Mutex<Queues>,
+}
+
+impl Scheduler {
+    /// Queues the job and waits for its response. Dropping the returned
+    /// future removes a queued job or aborts a running one.
+    pub async fn submit(self: &Arc<Self>, job: RequestJob) -> Result<EsResponse, AppError> {
+        let (method, path) = (job.method.clone(), job.path.clone());
+        let client = job.client.clone();
+        let response = self.enqueue(job);
+        if client.sniff_due() {
+            self.sniff(client);
+        }
+        response.await.unwrap_or_else(|_| {
+            Err(
+                AppError::new(ErrorKind::Cancelled, "Request dropped by the scheduler")
+                    .with_request(&method, &path),
+            )
+        })
+    }
+
+    // Refreshes the client's node list as a background job, so sniffing
+    // waits behind the user's requests like any other polling
+    fn sniff(self: &Arc<Self>, client: Arc<dyn EsClient>) {
+        let response = self.enqueue(RequestJob {
+            client: client.clone(),
+            priority: Priority::Background,
+            method: "GET".to_string(),
+            path: "/_nodes/http".to_string(),
+            body: None,
+            options: RequestOptions::default(),
+        });
+        tauri::async_runtime::spawn(async move {
+            match response.await {
+                Ok(Ok(response)) => match response.into_json() {
+                    Ok(nodes) => client.apply_sniff(&nodes),
+                    Err(e) => warn!("Sniffing failed: {}", e),
+                },
+                Ok(Err(e)) => warn!("Sniffing failed: {}", e),
+                Err(_) => {}
+            }
+        });
+    }
+
+    fn enqueue(
+        self: &Arc<Self>,
+        job: RequestJob,
+    ) -> oneshot::Receiver<Result<EsResponse, AppError>> {
+        let profile = job.client.profile();
+        // Unsaved profiles (e.g. while testing a connection) have no id yet
+        let cluster = if profile.id.is_empty() {
+            profile.url.clone()
+        } else {
+            profile.id.clone()
+        };
+        let max_concurrent = profile
+            .max_concurrent_requests
+            .map_or(DEFAULT_MAX_CONCURRENT, |n| n.max(1) as usize);
+
+        let priority = job.priority;
+        let (responder, response) = oneshot::channel();
+        {
+            let mut queues = self.lock();
+            let queue = queues.clusters.entry(cluster.clone()).or_default();
+            queue.max_concurrent = max_concurrent;
+            let queued = QueuedJob {
+                job,
+                enqueued: Instant::now(),
+                responder,
+            };
+            match priority {
+                Priority::Interactive => queue.interactive.push_back(queued),
+                Priority::Background => queue.background.push_back(queued),
+            }
+            if !queues.rotation.contains(&cluster) {
+                queues.rotation.push_back(cluster);
+            }
+        }
+        self.dispatch();
+        response
+    }
+
+    pub fn metrics(&self) -> Vec<ClusterMetrics> {
+        let queues = self.lock();
+        let mut metrics: Vec<ClusterMetrics> = queues
+            .clusters
+            .iter()
+            .map(|(cluster, queue)| ClusterMetrics {
+                cluster: cluster.clone(),
+                max_concurrent: queue.max_concurrent,
+                running: queue.running,
+                queued_interactive: queue.interactive.len(),
+                queued_background: queue.background.len(),
+                completed: queue.completed,
+                failed: queue.failed,
+                cancelled: queue.cancelled,
+                wait_p50_ms: percentile(&queue.waits, 50),
+                wait_p95_ms: percentile(&queue.waits, 95),
+                latency_p50_ms: percentile(&queue.latencies, 50),
+                latency_p95_ms: percentile(&queue.latencies, 95),
+            })
+            .collect();
+        metrics.sort_by(|a, b| a.cluster.cmp(&b.cluster));
+        metrics
+    }
+
+    fn dispatch(self: &Arc<Self>) {
+        let ready = self.lock().take_ready();
+        for (cluster, queued) in ready {
+            let scheduler = self.clone();
+            tauri::async_runtime::spawn(async move {
+                scheduler.run(cluster, queued).await;
+            });
+        }
+    }
+
+    async fn run(self: Arc<Self>, cluster: String, queued: QueuedJob) {
+        let QueuedJob {
+            job,
+            enqueued,
+            mut responder,
+        } = queued;
+        let wait = enqueued.elapsed();
+        debug!(
+            "Sending {} {} to {} after {:?} queued",
+            job.method, job.path, cluster, wait
+        );
+
+        let started = Instant::now();
+        // Stop as soon as the caller is gone, which aborts the HTTP request
+        let request = job
+            .client
+            .proxy_request(&job.method, &job.path, job.body, &job.options);
+        let result = tokio::select! {
+            result = request => Some(result),
+            _ = responder.closed() => None,
+        };
+
+        {
+            let mut queues = self.lock();
+            queues.running -= 1;
+            if let Some(queue) = queues.clusters.get_mut(&cluster) {
+                queue.running -= 1;
+                if job.priority == Priority::Background {
+                    queue.running_background -= 1;
+                }
+                match &result {
+                    Some(Ok(_)) => queue.completed += 1,
+                    Some(Err(_)) => queue.failed += 1,
+                    None => queue.cancelled += 1,
+                }
+                if result.is_some() {
+                    push_sample(&mut queue.waits, wait);
+                    push_sample(&mut queue.latencies, started.elapsed());
+                }
+            }
+        }
+        if let Some(result) = result {
+            let _ = responder.send(result);
+        }
+        self.dispatch();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Queues> {
+        self.queues.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn push_sample(samples: &mut VecDeque<Duration>, sample: Duration) {
+    if samples.len() == SAMPLE_SIZE {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
+
+fn percentile(samples: &VecDeque<Duration>, p: usize) -> Option<u64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted: Vec<Duration> = samples.iter().copied().collect();
+    sorted.sort();
+    let index = (sorted.len() - 1) * p / 100;
+    Some(sorted[index].as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::es::node_pool::NodeState;
+    use crate::es::response::ResponseBody;
+    use crate::models::connection::ConnectionProfile;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::sync::Semaphore;
+
+    // Records each request as it is sent and holds it until the test adds
+    // a permit to the gate
+    struct MockClient {
+        profile: ConnectionProfile,
+        gate: Arc<Semaphore>,
+        sent: Arc<Mutex<Vec<String>>>,
+        sniff: AtomicBool,
+        sniffed: Mutex<Option<Value>>,
+    }
+
+    impl MockClient {
+        fn new(id: &str, max_concurrent: u32, gate: &Arc<Semaphore>) -> Arc<Self> {
+            let profile = serde_json::from_value(serde_json::json!({
+                "id": id,
+                "name": id,
+                "url": "http://localhost:9200",
+                "auth_type": "basic",
+                "max_concurrent_requests": max_concurrent,
+            }))
+            .unwrap();
+            Arc::new(Self {
+                profile,
+                gate: gate.clone(),
+                sent: Arc::default(),
+                sniff: AtomicBool::new(false),
+                sniffed: Mutex::new(None),
+            })
+        }
+
+        fn sent(&self) -> Vec<String> {
+            self.sent.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl EsClient for MockClient {
+        async fn proxy_request(
+            &self,
+            method: &str,
+            path: &str,
+            _body: Option<Value>,
+            _options: &RequestOptions,
+        ) -> Result<EsResponse, AppError> {
+            self.sent.lock().unwrap().push(path.to_string());
+            self.gate.acquire().await.unwrap().forget();
+            Ok(EsResponse {
+                status: 200,
+                headers: Default::default(),
+                elapsed_ms: 0,
+                size: 0,
+                content_type: None,
+                body: ResponseBody::Json(serde_json::json!({ "nodes": {} })),
+                method: method.to_string(),
+                path: path.to_string(),
+            })
+        }
+
+        fn nodes(&self) -> Vec<NodeState> {
+            Vec::new()
+        }
+
+        fn profile(&self) -> &ConnectionProfile {
+            &self.profile
+        }
+
+        fn sniff_due(&self) -> bool {
+            self.sniff.swap(false, Ordering::SeqCst)
+        }
+
+        fn apply_sniff(&self, response: &Value) {
+            *self.sniffed.lock().unwrap() = Some(response.clone());
+        }
+    }
+
+    fn submit(
+        scheduler: &Arc<Scheduler>,
+        client: &Arc<MockClient>,
+        priority: Priority,
+        path: &str,
+    ) -> tokio::task::JoinHandle<Result<EsResponse, AppError>> {
+        let scheduler = scheduler.clone();
+        let job = RequestJob {
+            client: client.clone(),
+            priority,
+            method: "GET".to_string(),
+            path: path.to_string(),
+            body: None,
+            options: RequestOptions::default(),
+        };
+        tokio::spawn(async move { scheduler.submit(job).await })
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) {
+        for _ in 0..500 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("condition not reached");
+    }
+
+    fn queued(scheduler: &Scheduler, cluster: &str) -> (usize, usize) {
+        scheduler
+            .metrics()
+            .into_iter()
+            .find(|m| m.cluster == cluster)
+            .map_or((0, 0), |m| (m.queued_interactive, m.queued_background))
+    }
+
+    #[tokio::test]
+    async fn serves_interactive_jobs_before_queued_background_ones() {
+        let scheduler = Arc::new(Scheduler::default());
+        let gate = Arc::new(Semaphore::new(0));
+        let client = MockClient::new("prod", 1, &gate);
+
+        let first = submit(&scheduler, &client, Priority::Interactive, "/first");
+        wait_until(|| client.sent().len() == 1).await;
+        let background = submit(&scheduler, &client, Priority::Background, "/background");
+        wait_until(|| queued(&scheduler, "prod") == (0, 1)).await;
+        let interactive = submit(&scheduler, &client, Priority::Interactive, "/interactive");
+        wait_until(|| queued(&scheduler, "prod") == (1, 1)).await;
+
+        gate.add_permits(3);
+        for handle in [first, background, interactive] {
+            handle.await.unwrap().unwrap();
+        }
+        assert_eq!(client.sent(), ["/first", "/interactive", "/background"]);
+    }
+
+    #[tokio::test]
+    async fn busy_cluster_does_not_starve_others() {
+        let scheduler = Arc::new(Scheduler::default());
+        let gate = Arc::new(Semaphore::new(0));
+        let busy = MockClient::new("busy", MAX_CONCURRENT_TOTAL as u32, &gate);
+        let quiet = MockClient::new("quiet", 1, &gate);
+
+        // The busy cluster fills every global slot and keeps a backlog
+        let mut handles: Vec<_> = (0..MAX_CONCURRENT_TOTAL + 4)
+            .map(|i| {
+                submit(
+                    &scheduler,
+                    &busy,
+                    Priority::Interactive,
+                    &format!("/busy/{}", i),
+                )
+            })
+            .collect();
+        wait_until(|| queued(&scheduler, "busy") == (4, 0)).await;
+        handles.push(submit(&scheduler, &quiet, Priority::Interactive, "/quiet"));
+        wait_until(|| queued(&scheduler, "quiet") == (1, 0)).await;
+
+        // Freed slots alternate between the clusters, so the quiet one is
+        // served within a round rather than after the whole backlog
+        for _ in 0..2 {
+            gate.add_permits(1);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(quiet.sent(), ["/quiet"]);
+        assert!(queued(&scheduler, "busy").0 >= 2);
+
+        gate.add_permits(MAX_CONCURRENT_TOTAL + 4);
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn sniffs_as_a_background_job() {
+        let scheduler = Arc::new(Scheduler::default());
+        let gate = Arc::new(Semaphore::new(0));
+        let client = MockClient::new("prod", 1, &gate);
+        client.sniff.store(true, Ordering::SeqCst);
+
+        let request = submit(&scheduler, &client, Priority::Interactive, "/_search");
+        wait_until(|| queued(&scheduler, "prod") == (0, 1)).await;
+
+        gate.add_permits(2);
+        request.await.unwrap().unwrap();
+        wait_until(|| client.sniffed.lock().unwrap().is_some()).await;
+        assert_eq!(client.sent(), ["/_search", "/_nodes/http"]);
+    }
+}
@@ -0,0 +1,330 @@
+use crate::models::connection::ConnectionProfile;
+use anyhow::{Context, Result, anyhow, bail};
+use p12_keystore::KeyStore;
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::WebPkiSupportedAlgorithms;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Builds a rustls config from the TLS settings of a profile. Without
+/// any, this matches reqwest's defaults (webpki roots, no client cert).
+/// `server_name` overrides the name certificates are checked against,
+/// for connections made to another address such as a tunnel.
+pub fn build_tls_config(
+    profile: &ConnectionProfile,
+    server_name: Option<&str>,
+) -> Result<ClientConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+    let mut roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    if let Some(ca_path) = profile.ca_cert_path.as_deref().filter(|p| !p.is_empty()) {
+        for cert in CertificateDer::pem_file_iter(ca_path)
+            .with_context(|| format!("Failed to read CA bundle {}", ca_path))?
+        {
+            roots.add(cert?)?;
+        }
+    }
+
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+
+    let fingerprint = profile
+        .tls_fingerprint
+        .as_deref()
+        .filter(|f| !f.is_empty())
+        .map(parse_fingerprint)
+        .transpose()?;
+
+    let verifier: Arc<dyn ServerCertVerifier> = if fingerprint.is_some()
+        || profile.accept_invalid_certs
+    {
+        if profile.accept_invalid_certs {
+            warn!(
+                "Certificate verification disabled for profile {}",
+                profile.name
+            );
+        }
+        Arc::new(RelaxedVerifier {
+            fingerprint,
+            accept_invalid_certs: profile.accept_invalid_certs,
+            algorithms: provider.signature_verification_algorithms,
+        })
+    } else {
+        WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone()).build()?
+    };
+    let verifier: Arc<dyn ServerCertVerifier> = match server_name {
+        Some(name) => Arc::new(RenamedVerifier {
+            inner: verifier,
+            server_name: ServerName::try_from(name.to_string())
+                .with_context(|| format!("Invalid server name {}", name))?,
+        }),
+        None => verifier,
+    };
+    let builder = builder
+        .dangerous()
+        .with_custom_certificate_verifier(verifier);
+
+    let config = match profile
+        .client_cert_path
+        .as_deref()
+        .filter(|p| !p.is_empty())
+    {
+        Some(cert_path) => {
+            let (certs, key) = load_client_identity(profile, cert_path)?;
+            builder.with_client_auth_cert(certs, key)?
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    Ok(config)
+}
+
+fn load_client_identity(
+    profile: &ConnectionProfile,
+    cert_path: &str,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let is_pkcs12 = Path::new(cert_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("p12") || e.eq_ignore_ascii_case("pfx"));
+
+    if is_pkcs12 {
+        let data = std::fs::read(cert_path)
+            .with_context(|| format!("Failed to read client certificate {}", cert_path))?;
+        let password = profile.client_cert_password.as_deref().unwrap_or("");
+        let store = KeyStore::from_pkcs12(&data, password)?;
+        let (_, chain) = store
+            .private_key_chain()
+            .ok_or_else(|| anyhow!("No private key found in {}", cert_path))?;
+
+        let certs = chain
+            .chain()
+            .iter()
+            .map(|c| CertificateDer::from(c.as_der().to_vec()))
+            .collect();
+        let key = PrivatePkcs8KeyDer::from(chain.key().to_vec()).into();
+        return Ok((certs, key));
+    }
+
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .with_context(|| format!("Failed to read client certificate {}", cert_path))?
+        .collect::<Result<Vec<_>, _>>()?;
+    // The key may live in the certificate file itself
+    let key_path = profile
+        .client_key_path
+        .as_deref()
+        .filter(|p| !p.is_empty())
+        .unwrap_or(cert_path);
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .with_context(|| format!("Failed to read client key {}", key_path))?;
+    Ok((certs, key))
+}
+
+// Accepts "AB:CD:..." as printed by openssl, or plain hex
+fn parse_fingerprint(value: &str) -> Result<Vec<u8>> {
+    let cleaned: String = value
+        .chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .collect();
+    let bytes = hex::decode(&cleaned).context("Invalid certificate fingerprint")?;
+    if bytes.len() != 32 {
+        bail!("Certificate fingerprint must be a SHA-256 digest");
+    }
+    Ok(bytes)
+}
+
+/// Server verifier used when a fingerprint is pinned or verification is
+/// explicitly disabled. Handshake signatures are still checked either way.
+/// A pinned fingerprint may be that of any certificate in the chain, such
+/// as the HTTP CA fingerprint Elasticsearch prints at setup.
+#[derive(Debug)]
+struct RelaxedVerifier {
+    fingerprint: Option<Vec<u8>>,
+    accept_invalid_certs: bool,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for RelaxedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(expected) = &self.fingerprint {
+            let chain = std::iter::once(end_entity).chain(intermediates);
+            if !chain
+                .clone()
+                .any(|cert| Sha256::digest(cert.as_ref()).as_slice() == expected.as_slice())
+            {
+                let actual: Vec<String> = chain
+                    .map(|cert| hex::encode(Sha256::digest(cert.as_ref())))
+                    .collect();
+                return Err(rustls::Error::General(format!(
+                    "Certificate fingerprint mismatch: got {}",
+                    actual.join(", ")
+                )));
+            }
+            return Ok(ServerCertVerified::assertion());
+        }
+
+        if self.accept_invalid_certs {
+            return Ok(ServerCertVerified::assertion());
+        }
+
+        Err(rustls::Error::General(
+            "No certificate verification configured".to_string(),
+        ))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+/// Checks certificates against a fixed server name instead of the address
+/// connected to, e.g. the cluster's host behind an SSH tunnel.
+#[derive(Debug)]
+struct RenamedVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    server_name: ServerName<'static>,
+}
+
+impl ServerCertVerifier for RenamedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        // Only the tunnel's local end is renamed; other hosts reached with
+        // the same client, such as an OAuth2 token endpoint, keep theirs
+        let server_name = match server_name {
+            ServerName::IpAddress(ip) if std::net::IpAddr::from(*ip).is_loopback() => {
+                &self.server_name
+            }
+            _ => server_name,
+        };
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verifier(fingerprint: &[u8]) -> RelaxedVerifier {
+        RelaxedVerifier {
+            fingerprint: Some(fingerprint.to_vec()),
+            accept_invalid_certs: false,
+            algorithms: rustls::crypto::ring::default_provider().signature_verification_algorithms,
+        }
+    }
+
+    fn verify(
+        verifier: &RelaxedVerifier,
+        chain: &[CertificateDer<'_>],
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        verifier.verify_server_cert(
+            &chain[0],
+            &chain[1..],
+            &ServerName::try_from("localhost").unwrap(),
+            &[],
+            UnixTime::now(),
+        )
+    }
+
+    #[test]
+    fn parses_fingerprint_formats() {
+        let hex = "AB".repeat(32);
+        let colons = vec!["ab"; 32].join(":");
+        assert_eq!(parse_fingerprint(&hex).unwrap(), vec![0xab; 32]);
+        assert_eq!(parse_fingerprint(&colons).unwrap(), vec![0xab; 32]);
+        assert!(parse_fingerprint("abcd").is_err());
+        assert!(parse_fingerprint("not hex").is_err());
+    }
+
+    #[test]
+    fn matches_fingerprint_anywhere_in_chain() {
+        // The verifier only hashes certificates, so any bytes will do
+        let leaf = CertificateDer::from(b"leaf".to_vec());
+        let ca = CertificateDer::from(b"http ca".to_vec());
+        let chain = [leaf, ca];
+
+        let ca_pin = verifier(&Sha256::digest(b"http ca"));
+        assert!(verify(&ca_pin, &chain).is_ok());
+        let leaf_pin = verifier(&Sha256::digest(b"leaf"));
+        assert!(verify(&leaf_pin, &chain).is_ok());
+        let other = verifier(&Sha256::digest(b"other"));
+        assert!(verify(&other, &chain).is_err());
+    }
+
+    #[test]
+    fn ignores_blank_tls_fields() {
+        let profile: ConnectionProfile = serde_json::from_value(serde_json::json!({
+            "id": "tls",
+            "name": "tls",
+            "url": "https://localhost:9200",
+            "auth_type": "none",
+            "ca_cert_path": "",
+            "client_cert_path": "",
+            "client_key_path": "",
+            "tls_fingerprint": "",
+        }))
+        .unwrap();
+        assert!(build_tls_config(&profile, None).is_ok());
+    }
+}
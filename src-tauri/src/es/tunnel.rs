@@ -0,0 +1,185 @@
+use crate::models::connection::ConnectionProfile;
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::VecDeque;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tracing::{debug, info};
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(20);
+// Lines of ssh output kept to explain an early exit
+const STDERR_LINES: usize = 20;
+
+/// A local port forward through the system `ssh` client, so keys, the agent,
+/// jump hosts and known_hosts behave exactly as on the command line.
+/// The ssh process is killed when the tunnel is dropped.
+pub struct SshTunnel {
+    local_addr: SocketAddr,
+    _child: Child,
+}
+
+impl SshTunnel {
+    pub async fn open(profile: &ConnectionProfile) -> Result<Self> {
+        let ssh_host = profile
+            .ssh_host
+            .as_deref()
+            .ok_or_else(|| anyhow!("Missing SSH host"))?;
+        check_arg("SSH host", ssh_host)?;
+
+        let url = profile.resolved_url().map_err(|e| anyhow!(e))?;
+        let target = reqwest::Url::parse(&url).context("Invalid cluster URL")?;
+        let target_host = target
+            .host_str()
+            .ok_or_else(|| anyhow!("Cluster URL has no host"))?;
+        let target_port = target
+            .port_or_known_default()
+            .ok_or_else(|| anyhow!("Cluster URL has no port"))?;
+
+        // Grab a free local port; ssh binds it right after
+        let local_port = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?
+            .local_addr()?
+            .port();
+        let local_addr = SocketAddr::from((Ipv4Addr::LOCALHOST, local_port));
+
+        let mut cmd = Command::new("ssh");
+        cmd.arg("-N")
+            .arg("-L")
+            .arg(format!(
+                "127.0.0.1:{}:{}:{}",
+                local_port, target_host, target_port
+            ))
+            .args(["-o", "ExitOnForwardFailure=yes"])
+            .args(["-o", "BatchMode=yes"])
+            .args(["-o", "ServerAliveInterval=30"]);
+        if let Some(port) = profile.ssh_port {
+            cmd.arg("-p").arg(port.to_string());
+        }
+        // Without a key file ssh falls back to the agent
+        if let Some(key) = profile.ssh_key_path.as_deref().filter(|k| !k.is_empty()) {
+            cmd.arg("-i").arg(key).args(["-o", "IdentitiesOnly=yes"]);
+        }
+        if !profile.ssh_jump_hosts.is_empty() {
+            for jump_host in &profile.ssh_jump_hosts {
+                check_arg("SSH jump host", jump_host)?;
+            }
+            cmd.arg("-J").arg(profile.ssh_jump_hosts.join(","));
+        }
+        let destination = match profile.ssh_user.as_deref().filter(|u| !u.is_empty()) {
+            Some(user) => {
+                check_arg("SSH user", user)?;
+                format!("{}@{}", user, ssh_host)
+            }
+            None => ssh_host.to_string(),
+        };
+        // Nothing after `--` is read as an option
+        cmd.arg("--")
+            .arg(destination)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        info!(
+            "Opening SSH tunnel via {} to {}:{} on {}",
+            ssh_host, target_host, target_port, local_addr
+        );
+        let mut child = cmd.spawn().context("Failed to start ssh")?;
+
+        // Drained for as long as ssh runs so it never blocks on a full pipe
+        let mut stderr = child.stderr.take().map(|pipe| {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(pipe).lines();
+                let mut last = VecDeque::with_capacity(STDERR_LINES);
+                while let Ok(Some(line)) = lines.next_line().await {
+                    debug!("ssh: {}", line);
+                    if last.len() == STDERR_LINES {
+                        last.pop_front();
+                    }
+                    last.push_back(line);
+                }
+                Vec::from(last).join("\n")
+            })
+        });
+
+        // Ready once the forwarded port accepts connections
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                let output = match stderr.take() {
+                    Some(task) => task.await.unwrap_or_default(),
+                    None => String::new(),
+                };
+                bail!("ssh exited with {}: {}", status, output.trim());
+            }
+            if TcpStream::connect(local_addr).await.is_ok() {
+                break;
+            }
+            if Instant::now() >= deadline {
+                bail!("Timed out waiting for the port forward via {}", ssh_host);
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+
+        Ok(Self {
+            local_addr,
+            _child: child,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+// ssh reads any argument starting with '-' as an option, which for a host
+// such as `-oProxyCommand=...` means running a local command
+fn check_arg(name: &str, value: &str) -> Result<()> {
+    if value.trim_start().starts_with('-') {
+        bail!("{} cannot start with '-': {}", name, value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(ssh_host: &str, ssh_user: &str, jump_hosts: &[&str]) -> ConnectionProfile {
+        serde_json::from_value(serde_json::json!({
+            "id": "ssh",
+            "name": "ssh",
+            "url": "https://10.0.0.5:9200",
+            "auth_type": "none",
+            "ssh_host": ssh_host,
+            "ssh_user": ssh_user,
+            "ssh_jump_hosts": jump_hosts,
+        }))
+        .unwrap()
+    }
+
+    async fn open_error(profile: &ConnectionProfile) -> String {
+        match SshTunnel::open(profile).await {
+            Ok(_) => panic!("tunnel opened"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_option_like_arguments() {
+        let err = open_error(&profile("-oProxyCommand=touch /tmp/x", "", &[])).await;
+        assert!(err.starts_with("SSH host cannot start with '-'"), "{}", err);
+
+        let err = open_error(&profile("bastion", " -oProxyCommand=x", &[])).await;
+        assert!(err.starts_with("SSH user cannot start with '-'"), "{}", err);
+
+        let err = open_error(&profile("bastion", "", &["jump", "-oProxyCommand=x"])).await;
+        assert!(
+            err.starts_with("SSH jump host cannot start with '-'"),
+            "{}",
+            err
+        );
+    }
+}
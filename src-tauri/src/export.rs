@@ -0,0 +1,306 @@
+use crate::db::{Database, derive_key};
+use crate::models::connection::ConnectionProfile;
+use crate::team_config;
+use anyhow::{Context, Result, anyhow, bail};
+use magic_crypt::{MagicCryptTrait, new_magic_crypt};
+use serde::{Deserialize, Serialize};
+
+const FORMAT: &str = "ruskview-profiles";
+const VERSION: u32 = 1;
+
+// On-disk layout: the profile list as JSON, encrypted with a key derived
+// from the passphrase and a per-file salt.
+#[derive(Serialize, Deserialize)]
+struct ExportFile {
+    format: String,
+    version: u32,
+    salt: String,
+    data: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    KeepBoth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportAction {
+    Add,
+    Overwrite,
+    Skip,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportPreviewEntry {
+    pub id: String,
+    pub name: String,
+    pub action: ImportAction,
+    // The stored profile that matches by id or name
+    pub existing_id: Option<String>,
+    pub has_secrets: bool,
+    // Runs on every connect, so the user must see it before importing
+    pub credential_helper: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub overwritten: usize,
+    pub skipped: usize,
+}
+
+pub fn encrypt_profiles(profiles: &[ConnectionProfile], passphrase: &str) -> Result<String> {
+    if passphrase.is_empty() {
+        bail!("Passphrase must not be empty");
+    }
+
+    let salt = uuid::Uuid::new_v4();
+    let mc = new_magic_crypt!(derive_key(passphrase, salt.as_bytes())?, 256);
+    let plain = serde_json::to_string(profiles)?;
+
+    let file = ExportFile {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        salt: hex::encode(salt.as_bytes()),
+        data: mc.encrypt_str_to_base64(plain),
+    };
+    Ok(serde_json::to_string_pretty(&file)?)
+}
+
+pub fn decrypt_profiles(contents: &str, passphrase: &str) -> Result<Vec<ConnectionProfile>> {
+    let file: ExportFile =
+        serde_json::from_str(contents).context("Not a Ruskview profile export")?;
+    if file.format != FORMAT {
+        bail!("Not a Ruskview profile export");
+    }
+    if file.version > VERSION {
+        bail!("Export version {} is not supported", file.version);
+    }
+
+    let mc = new_magic_crypt!(derive_key(passphrase, &hex::decode(&file.salt)?)?, 256);
+    let plain = mc
+        .decrypt_base64_to_string(&file.data)
+        .map_err(|_| anyhow!("Wrong passphrase or corrupted file"))?;
+    serde_json::from_str(&plain).map_err(|_| anyhow!("Wrong passphrase or corrupted file"))
+}
+
+fn find_existing<'a>(
+    profile: &ConnectionProfile,
+    existing: &'a [ConnectionProfile],
+) -> Option<&'a ConnectionProfile> {
+    existing
+        .iter()
+        .find(|p| p.id == profile.id)
+        .or_else(|| existing.iter().find(|p| p.name == profile.name))
+}
+
+/// What `import_profiles` would do with each incoming profile under `policy`.
+pub fn preview_import(
+    incoming: &[ConnectionProfile],
+    existing: &[ConnectionProfile],
+    policy: ConflictPolicy,
+) -> Vec<ImportPreviewEntry> {
+    incoming
+        .iter()
+        .map(|profile| {
+            let matched = find_existing(profile, existing);
+            ImportPreviewEntry {
+                id: profile.id.clone(),
+                name: profile.name.clone(),
+                action: match (matched, policy) {
+                    (None, _) | (Some(_), ConflictPolicy::KeepBoth) => ImportAction::Add,
+                    (Some(_), ConflictPolicy::Skip) => ImportAction::Skip,
+                    (Some(_), ConflictPolicy::Overwrite) => ImportAction::Overwrite,
+                },
+                existing_id: matched.map(|p| p.id.clone()),
+                has_secrets: profile.without_secrets() != *profile,
+                credential_helper: profile
+                    .credential_helper
+                    .clone()
+                    .filter(|c| !c.trim().is_empty()),
+            }
+        })
+        .collect()
+}
+
+/// Saves the selected (or all, when `ids` is None) incoming profiles in one
+/// transaction, resolving clashes with stored ones by id first, then by
+/// name. An overwrite keeps the stored secrets the incoming profile lacks.
+pub fn import_profiles(
+    db: &Database,
+    incoming: Vec<ConnectionProfile>,
+    policy: ConflictPolicy,
+    ids: Option<&[String]>,
+) -> Result<ImportSummary> {
+    let existing = db.get_profiles()?;
+    let mut summary = ImportSummary::default();
+    let mut imported = Vec::new();
+
+    for mut profile in incoming {
+        if ids.is_some_and(|ids| !ids.contains(&profile.id)) {
+            continue;
+        }
+        // Those ids belong to team config profiles, which cannot be stored
+        if team_config::is_file_profile(&profile.id) {
+            profile.id = uuid::Uuid::new_v4().to_string();
+            profile.read_only = false;
+            profile.source = None;
+        }
+
+        match (find_existing(&profile, &existing), policy) {
+            (None, _) => summary.added += 1,
+            (Some(_), ConflictPolicy::Skip) => {
+                summary.skipped += 1;
+                continue;
+            }
+            (Some(matched), ConflictPolicy::Overwrite) => {
+                profile.id = matched.id.clone();
+                profile.keep_secrets_from(matched);
+                summary.overwritten += 1;
+            }
+            (Some(_), ConflictPolicy::KeepBoth) => {
+                profile.id = uuid::Uuid::new_v4().to_string();
+                profile.name = format!("{} (imported)", profile.name);
+                summary.added += 1;
+            }
+        }
+
+        imported.push(profile);
+    }
+
+    db.save_profiles(&imported)?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn profile(id: &str, name: &str, password: Option<&str>) -> ConnectionProfile {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "url": format!("https://{}:9200", id),
+            "auth_type": "basic",
+            "username": "elastic",
+            "password": password,
+        }))
+        .unwrap()
+    }
+
+    // A fresh database in its own directory, removed when dropped
+    struct TempDb {
+        dir: PathBuf,
+        db: Database,
+    }
+
+    impl TempDb {
+        fn new(profiles: &[ConnectionProfile]) -> Self {
+            let dir = std::env::temp_dir().join(format!("ruskview-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let db = Database::init(&dir).unwrap();
+            db.save_profiles(profiles).unwrap();
+            Self { dir, db }
+        }
+
+        fn profile(&self, id: &str) -> Option<ConnectionProfile> {
+            self.db
+                .get_profiles()
+                .unwrap()
+                .into_iter()
+                .find(|p| p.id == id)
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn round_trips_through_encryption() {
+        let profiles = vec![
+            profile("prod", "Prod", Some("s3cret")),
+            profile("dev", "Dev", None),
+        ];
+        let contents = encrypt_profiles(&profiles, "correct horse").unwrap();
+        assert!(!contents.contains("s3cret"));
+        assert_eq!(
+            decrypt_profiles(&contents, "correct horse").unwrap(),
+            profiles
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_passphrase() {
+        let contents = encrypt_profiles(&[profile("prod", "Prod", None)], "right").unwrap();
+        let err = decrypt_profiles(&contents, "wrong").unwrap_err();
+        assert_eq!(err.to_string(), "Wrong passphrase or corrupted file");
+        assert!(encrypt_profiles(&[], "").is_err());
+        assert!(decrypt_profiles("{}", "right").is_err());
+    }
+
+    #[test]
+    fn applies_conflict_policies() {
+        let stored = profile("prod", "Prod", Some("stored"));
+        let mut incoming = profile("prod", "Prod", None);
+        incoming.url = "https://new:9200".to_string();
+
+        let temp = TempDb::new(std::slice::from_ref(&stored));
+        let summary =
+            import_profiles(&temp.db, vec![incoming.clone()], ConflictPolicy::Skip, None).unwrap();
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(temp.profile("prod").unwrap(), stored);
+
+        let summary = import_profiles(
+            &temp.db,
+            vec![incoming.clone()],
+            ConflictPolicy::Overwrite,
+            None,
+        )
+        .unwrap();
+        assert_eq!(summary.overwritten, 1);
+        let overwritten = temp.profile("prod").unwrap();
+        assert_eq!(overwritten.url, "https://new:9200");
+        // The export had no secrets, so the stored one stays
+        assert_eq!(overwritten.password.as_deref(), Some("stored"));
+
+        let summary =
+            import_profiles(&temp.db, vec![incoming], ConflictPolicy::KeepBoth, None).unwrap();
+        assert_eq!(summary.added, 1);
+        let profiles = temp.db.get_profiles().unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert!(profiles.iter().any(|p| p.name == "Prod (imported)"));
+    }
+
+    #[test]
+    fn gives_team_config_ids_fresh_ones() {
+        let temp = TempDb::new(&[]);
+        let mut incoming = profile("file:Shared", "Shared", None);
+        incoming.read_only = true;
+        import_profiles(&temp.db, vec![incoming], ConflictPolicy::Skip, None).unwrap();
+
+        let profiles = temp.db.get_profiles().unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert!(!team_config::is_file_profile(&profiles[0].id));
+        assert!(!profiles[0].read_only);
+    }
+
+    #[test]
+    fn preview_shows_credential_helpers() {
+        let mut incoming = profile("prod", "Prod", None);
+        incoming.credential_helper = Some("vault read -field=password secret/es".to_string());
+        let preview = preview_import(&[incoming], &[], ConflictPolicy::Skip);
+        assert_eq!(preview[0].action, ImportAction::Add);
+        assert_eq!(
+            preview[0].credential_helper.as_deref(),
+            Some("vault read -field=password secret/es")
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use crate::commands;
+use crate::error::{AppError, ErrorKind};
+use crate::es::client::{EsClient, RequestOptions};
+use crate::es::retry;
+use crate::es::scheduler::{Priority, RequestJob};
+use crate::models::connection::RetryPolicy;
+use crate::state::{AppState, Session};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{debug, info, warn};
+
+// Time between pings of a healthy session
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+// Includes the time queued behind other background requests
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+// A ping slower than this marks the session degraded
+const SLOW_PING: Duration = Duration::from_secs(5);
+// Time before pinging again after a failed ping
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+// Failed pings in a row before a session counts as unreachable
+const UNREACHABLE_AFTER: u32 = 3;
+const RECONNECT_INITIAL_BACKOFF_MS: u64 = 1_000;
+const RECONNECT_MAX_BACKOFF_MS: u64 = 60_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionStatus {
+    Connected,
+    // Pings are slow, or the last few failed
+    Degraded,
+    // Reconnecting with backoff
+    Unreachable,
+    // Pings answered 401 or 403
+    AuthExpired,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionHealth {
+    pub status: ConnectionStatus,
+    // `green`, `yellow` or `red` as of the last answered ping
+    pub color: Option<String>,
+    // Unix time in milliseconds of the last ping
+    pub checked_at: Option<u64>,
+    // Why the last ping failed
+    pub error: Option<String>,
+}
+
+impl SessionHealth {
+    pub fn connected() -> Self {
+        Self {
+            status: ConnectionStatus::Connected,
+            color: None,
+            checked_at: None,
+            error: None,
+        }
+    }
+}
+
+/// Payload of the `session-health` event, emitted when a session's status
+/// or cluster color changes.
+#[derive(Debug, Clone, Serialize)]
+struct HealthEvent<'a> {
+    session_id: &'a str,
+    previous: SessionHealth,
+    health: SessionHealth,
+}
+
+/// Pings the session's cluster until the session is closed or replaced.
+/// Unreachable sessions, and expired ones whose credentials come from a
+/// helper, reconnect with backoff: a new client, tunnel and credentials.
+pub fn watch(app: AppHandle, session: Arc<Session>) {
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let session_id = session.info.session_id.clone();
+        // Only a credential helper can hand out new credentials
+        let has_helper = session
+            .profile
+            .credential_helper
+            .as_deref()
+            .is_some_and(|c| !c.trim().is_empty());
+        let mut session = session;
+        let mut failures = 0;
+        // Check right away to learn the cluster color
+        let mut delay = Duration::ZERO;
+
+        loop {
+            tokio::time::sleep(delay).await;
+            if !state.sessions.read().await.is_current(&session) {
+                break;
+            }
+
+            let previous = session.health();
+            let started = Instant::now();
+            let result = if previous.status == ConnectionStatus::Unreachable
+                || (previous.status == ConnectionStatus::AuthExpired && has_helper)
+            {
+                reconnect(&state, &mut session).await
+            } else {
+                ping(&state, &session.client).await
+            };
+
+            let mut health = SessionHealth {
+                checked_at: Some(chrono::Utc::now().timestamp_millis() as u64),
+                ..previous.clone()
+            };
+            match result {
+                Ok(color) => {
+                    failures = 0;
+                    health.status = if started.elapsed() > SLOW_PING {
+                        ConnectionStatus::Degraded
+                    } else {
+                        ConnectionStatus::Connected
+                    };
+                    health.color = Some(color);
+                    health.error = None;
+                }
+                Err(e) => {
+                    failures += 1;
+                    health.status = if e.kind == ErrorKind::Auth {
+                        ConnectionStatus::AuthExpired
+                    } else if failures >= UNREACHABLE_AFTER {
+                        ConnectionStatus::Unreachable
+                    } else {
+                        ConnectionStatus::Degraded
+                    };
+                    health.error = Some(e.to_string());
+                }
+            }
+            delay = match health.status {
+                _ if failures == 0 => PING_INTERVAL,
+                ConnectionStatus::Degraded => RETRY_INTERVAL,
+                ConnectionStatus::Unreachable => {
+                    reconnect_backoff(failures - UNREACHABLE_AFTER + 1)
+                }
+                ConnectionStatus::AuthExpired if has_helper => reconnect_backoff(failures),
+                _ => PING_INTERVAL,
+            };
+
+            session.set_health(health.clone());
+            if health.status != previous.status || health.color != previous.color {
+                info!(
+                    "Session {} is {:?} ({:?})",
+                    session_id, health.status, health.color
+                );
+                let event = HealthEvent {
+                    session_id: &session_id,
+                    previous,
+                    health,
+                };
+                if let Err(e) = app.emit("session-health", event) {
+                    warn!("Failed to emit session health: {}", e);
+                }
+            }
+        }
+        debug!("Stopped watching session {}", session_id);
+    });
+}
+
+// `_cluster/health` through the scheduler, returning the cluster's color
+async fn ping(state: &AppState, client: &Arc<dyn EsClient>) -> Result<String, AppError> {
+    let job = RequestJob {
+        client: client.clone(),
+        priority: Priority::Background,
+        method: "GET".to_string(),
+        path: "/_cluster/health".to_string(),
+        body: None,
+        options: RequestOptions {
+            timeout: Some(PING_TIMEOUT),
+            ..RequestOptions::default()
+        },
+    };
+    let health = tokio::time::timeout(PING_TIMEOUT, state.scheduler.submit(job))
+        .await
+        .map_err(|_| {
+            AppError::new(ErrorKind::Timeout, "Health check timed out")
+                .with_request("GET", "/_cluster/health")
+        })??
+        .into_json()?;
+    Ok(health
+        .get("status")
+        .and_then(|s| s.as_str())
+        .unwrap_or("unknown")
+        .to_string())
+}
+
+// Builds a new client with fresh credentials and makes it the session's
+// once it answers a ping
+async fn reconnect(state: &AppState, session: &mut Arc<Session>) -> Result<String, AppError> {
+    debug!("Reconnecting session {}", session.info.session_id);
+    state
+        .credential_helper
+        .invalidate(&session.profile.id)
+        .await;
+    let client = commands::build_client(session.profile.clone(), state).await?;
+    let color = ping(state, &client).await?;
+    // None when the session was closed meanwhile, which ends the watcher
+    if let Some(reconnected) = state.sessions.write().await.replace(session, client) {
+        info!("Reconnected session {}", session.info.session_id);
+        *session = reconnected;
+    }
+    Ok(color)
+}
+
+fn reconnect_backoff(attempt: u32) -> Duration {
+    let policy = RetryPolicy {
+        initial_backoff_ms: RECONNECT_INITIAL_BACKOFF_MS,
+        max_backoff_ms: RECONNECT_MAX_BACKOFF_MS,
+        ..RetryPolicy::default()
+    };
+    retry::backoff(&policy, attempt)
+}
@@ -1,16 +1,23 @@
 mod auth;
 mod commands;
 mod db;
+mod error;
 mod es;
+mod export;
+mod health;
 mod models;
 mod state;
+mod team_config;
 
-use state::{AppState, RequestJob};
+// Lets the benches time the shipped response handling
+#[doc(hidden)]
+pub use es::response;
+
+use state::AppState;
 use tauri::{
-    Manager,
+    Emitter, Manager,
     menu::{Menu, MenuItem, PredefinedMenuItem, Submenu},
 };
-use tokio::sync::mpsc;
 use tracing::{Level, error, info};
 use tracing_subscriber::FmtSubscriber;
 
@@ -65,20 +72,36 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             commands::connect_to_cluster,
+            commands::list_sessions,
+            commands::disconnect,
             commands::proxy_request,
+            commands::proxy_request_raw,
+            commands::proxy_request_stream,
+            commands::cancel_request,
+            commands::get_node_pool,
+            commands::get_scheduler_metrics,
+            commands::get_pool_stats,
+            commands::set_pool_config,
             commands::test_connection,
+            commands::resolve_cloud_id,
             commands::perform_cluster_op,
             commands::perform_index_op,
             commands::save_profile,
             commands::get_profiles,
-            commands::delete_profile
+            commands::delete_profile,
+            commands::get_store_status,
+            commands::unlock_store,
+            commands::lock_store,
+            commands::set_master_password,
+            commands::set_idle_timeout,
+            commands::export_profiles,
+            commands::preview_import,
+            commands::import_profiles,
+            commands::set_team_config_path
         ])
         .setup(|app| {
             let handle = app.handle();
 
-            // Initialize Task Queue
-            let (tx, mut rx) = mpsc::channel::<RequestJob>(100);
-
             // Initialize DB
             let app_handle = app.handle();
             let app_dir = app_handle
@@ -88,112 +111,55 @@ pub fn run() {
             std::fs::create_dir_all(&app_dir).expect("failed to create app data dir");
             let db = db::Database::init(&app_dir).ok();
 
+            // RUSKVIEW_CONFIG overrides the team config path saved in the DB
+            let team_config_path = std::env::var("RUSKVIEW_CONFIG").ok().or_else(|| {
+                db.as_ref()
+                    .and_then(|db| db.team_config_path().ok().flatten())
+            });
+
             // Initialize AppState
-            app.manage(AppState::new(tx, db));
+            app.manage(AppState::new(db));
 
-            // Spawn Worker
+            // Load the shared ruskview.toml and pick up edits to it
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
-                info!("Worker thread started");
-                // We need access to the client, but AppState is managed by Tauri.
-                // However, we can't access AppState inside this closure easily before it's managed.
-                // So we'll create the client here or pass it.
-                // Better approach: Create AppState first, then spawn worker that uses it?
-                // Or just let the worker have its own client reference?
-                // Since we want to use the SAME client pool, we should probably initialize AppState manually first.
-
-                // Actually, let's just create the client here and share it if needed,
-                // OR just let the worker own the client for processing requests.
-                // The commands only need to send to the queue.
-
-                let client = reqwest::Client::builder()
-                    .pool_idle_timeout(std::time::Duration::from_secs(90))
-                    .pool_max_idle_per_host(10)
-                    .build()
-                    .expect("Failed to create HTTP client");
-
-                while let Some(job) = rx.recv().await {
-                    let client = client.clone();
-                    // We need the current profile URL/Auth.
-                    // This is tricky because the profile is in AppState (RwLock).
-                    // We can pass the FULL request details in the Job, including the base URL and Auth headers.
-                    // But the prompt says "http client pool is the consumer".
-
-                    // Let's assume the Job contains everything needed to make the request
-                    // OR the worker can access the state.
-                    // Accessing Tauri state from a spawned tokio task is possible if we pass the app handle.
-
-                    let state = app_handle.state::<AppState>();
-                    let profile_guard = state.current_profile.read().await;
-
-                    let active_profile = if let Some(p) = &job.profile_override {
-                        Some(p.clone())
-                    } else {
-                        profile_guard.clone()
-                    };
-
-                    if let Some(profile) = active_profile {
-                        let url = format!("{}{}", profile.url.trim_end_matches('/'), job.path);
-                        info!("Processing request: {} {}", job.method, url);
-
-                        let mut req_builder = client
-                            .request(job.method.parse().unwrap_or(reqwest::Method::GET), &url);
+                let state = app_handle.state::<AppState>();
+                if let Err(e) = state
+                    .team_config
+                    .set_path(team_config_path.map(std::path::PathBuf::from))
+                    .await
+                {
+                    error!("Failed to load team config: {}", e);
+                }
 
-                        if let Some(body) = job.body {
-                            req_builder = req_builder.json(&body);
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(2));
+                loop {
+                    interval.tick().await;
+                    match state.team_config.reload_if_changed().await {
+                        Ok(true) => {
+                            let _ = app_handle.emit("profiles-changed", ());
                         }
+                        Ok(false) => {}
+                        Err(e) => error!("Failed to reload team config: {}", e),
+                    }
+                }
+            });
 
-                        // Add Auth (Basic or AWS)
-                        if profile.auth_type == "basic" {
-                            if let (Some(u), Some(p)) = (&profile.username, &profile.password) {
-                                req_builder = req_builder.basic_auth(u, Some(p));
+            // Lock the profile store after the configured idle time
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+                loop {
+                    interval.tick().await;
+                    let state = app_handle.state::<AppState>();
+                    if state.idle_lock_due() {
+                        info!("Idle timeout reached, locking profile store");
+                        match state.lock_store().await {
+                            Ok(()) => {
+                                let _ = app_handle.emit("store-locked", ());
                             }
+                            Err(e) => error!("Failed to lock profile store: {}", e),
                         }
-                        // TODO: Add AWS SigV4 support here using profile.region, access_key, secret_key
-
-                        let result = match req_builder.send().await {
-                            Ok(res) => {
-                                let status = res.status();
-                                info!("Response status: {}", status);
-
-                                match res.text().await {
-                                    Ok(text) => {
-                                        // Log the first 500 chars of the response for debugging
-                                        let log_text = if text.len() > 500 {
-                                            format!("{}...", &text[..500])
-                                        } else {
-                                            text.clone()
-                                        };
-                                        info!("Response body preview: {}", log_text);
-
-                                        match serde_json::from_str::<serde_json::Value>(&text) {
-                                            Ok(json) => Ok(json),
-                                            Err(e) => {
-                                                error!("Failed to parse JSON: {}", e);
-                                                Err(format!(
-                                                    "Failed to parse JSON: {}. Body: {}",
-                                                    e, log_text
-                                                ))
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        error!("Failed to read response text: {}", e);
-                                        Err(e.to_string())
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                error!("Request failed: {}", e);
-                                Err(e.to_string())
-                            }
-                        };
-
-                        let _ = job.responder.send(result);
-                    } else {
-                        let _ = job
-                            .responder
-                            .send(Err("No active connection profile".to_string()));
                     }
                 }
             });
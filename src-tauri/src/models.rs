@@ -1 +1,2 @@
+pub mod cloud_id;
 pub mod connection;
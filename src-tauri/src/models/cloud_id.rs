@@ -0,0 +1,150 @@
+use base64::prelude::*;
+use serde::Serialize;
+
+const DEFAULT_PORT: u16 = 443;
+
+/// A decoded Elastic Cloud ID: `<deployment>:base64(<host>$<es_uuid>$<kibana_uuid>)`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CloudId {
+    pub deployment_name: String,
+    pub host: String,
+    pub port: u16,
+    pub es_uuid: String,
+    pub kibana_uuid: Option<String>,
+    // Elasticsearch endpoint the ID resolves to
+    pub url: String,
+}
+
+impl CloudId {
+    pub fn parse(cloud_id: &str) -> Result<Self, String> {
+        let cloud_id = cloud_id.trim();
+        // The deployment name is optional and may itself contain colons
+        let (deployment_name, encoded) = match cloud_id.rsplit_once(':') {
+            Some((name, encoded)) => (name.to_string(), encoded),
+            None => (String::new(), cloud_id),
+        };
+
+        let decoded = BASE64_STANDARD
+            .decode(encoded)
+            .map_err(|e| format!("Invalid Cloud ID: {}", e))?;
+        let decoded = String::from_utf8(decoded).map_err(|e| format!("Invalid Cloud ID: {}", e))?;
+
+        let mut parts = decoded.split('$');
+        let host_part = parts.next().unwrap_or_default();
+        let es_part = parts
+            .next()
+            .filter(|p| !p.is_empty())
+            .ok_or_else(|| "Invalid Cloud ID: missing Elasticsearch UUID".to_string())?;
+        let kibana_uuid = parts
+            .next()
+            .filter(|p| !p.is_empty())
+            .map(|p| p.split(':').next().unwrap_or(p).to_string());
+
+        if host_part.is_empty() {
+            return Err("Invalid Cloud ID: missing host".to_string());
+        }
+
+        // The port may be attached to the host or, in newer IDs, to the UUID
+        let (host, host_port) = split_port(host_part)?;
+        let (es_uuid, es_port) = split_port(es_part)?;
+        let port = es_port.or(host_port).unwrap_or(DEFAULT_PORT);
+        let url = if port == DEFAULT_PORT {
+            format!("https://{}.{}", es_uuid, host)
+        } else {
+            format!("https://{}.{}:{}", es_uuid, host, port)
+        };
+
+        Ok(Self {
+            deployment_name,
+            host: host.to_string(),
+            port,
+            es_uuid: es_uuid.to_string(),
+            kibana_uuid,
+            url,
+        })
+    }
+}
+
+fn split_port(value: &str) -> Result<(&str, Option<u16>), String> {
+    match value.rsplit_once(':') {
+        Some((name, port)) => {
+            let port = port
+                .parse()
+                .map_err(|_| format!("Invalid Cloud ID: bad port '{}'", port))?;
+            Ok((name, Some(port)))
+        }
+        None => Ok((value, None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(decoded: &str) -> String {
+        BASE64_STANDARD.encode(decoded)
+    }
+
+    #[test]
+    fn parses_named_id() {
+        let id = CloudId::parse(&format!(
+            "my-deployment:{}",
+            encode("us-east1.gcp.elastic-cloud.com$abcd1234$efgh5678")
+        ))
+        .unwrap();
+        assert_eq!(id.deployment_name, "my-deployment");
+        assert_eq!(id.host, "us-east1.gcp.elastic-cloud.com");
+        assert_eq!(id.port, 443);
+        assert_eq!(id.es_uuid, "abcd1234");
+        assert_eq!(id.kibana_uuid.as_deref(), Some("efgh5678"));
+        assert_eq!(id.url, "https://abcd1234.us-east1.gcp.elastic-cloud.com");
+    }
+
+    #[test]
+    fn deployment_name_is_optional_and_may_contain_colons() {
+        let encoded = encode("eu-west-1.aws.found.io$abcd1234");
+        let id = CloudId::parse(&encoded).unwrap();
+        assert_eq!(id.deployment_name, "");
+        assert_eq!(id.kibana_uuid, None);
+
+        let id = CloudId::parse(&format!("team:prod:{}", encoded)).unwrap();
+        assert_eq!(id.deployment_name, "team:prod");
+        assert_eq!(id.url, "https://abcd1234.eu-west-1.aws.found.io");
+    }
+
+    #[test]
+    fn reads_port_from_host_or_uuid() {
+        let id = CloudId::parse(&encode("example.com:9243$abcd1234")).unwrap();
+        assert_eq!(id.host, "example.com");
+        assert_eq!(id.port, 9243);
+        assert_eq!(id.url, "https://abcd1234.example.com:9243");
+
+        // The UUID's port wins over the host's
+        let id = CloudId::parse(&encode("example.com:9243$abcd1234:9200$efgh5678:9201")).unwrap();
+        assert_eq!(id.es_uuid, "abcd1234");
+        assert_eq!(id.port, 9200);
+        assert_eq!(id.kibana_uuid.as_deref(), Some("efgh5678"));
+        assert_eq!(id.url, "https://abcd1234.example.com:9200");
+    }
+
+    #[test]
+    fn rejects_bad_port() {
+        let err = CloudId::parse(&encode("example.com:https$abcd1234")).unwrap_err();
+        assert!(err.contains("bad port 'https'"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_missing_parts() {
+        let err = CloudId::parse(&encode("example.com")).unwrap_err();
+        assert!(err.contains("missing Elasticsearch UUID"), "{}", err);
+        let err = CloudId::parse(&encode("example.com$")).unwrap_err();
+        assert!(err.contains("missing Elasticsearch UUID"), "{}", err);
+        let err = CloudId::parse(&encode("$abcd1234")).unwrap_err();
+        assert!(err.contains("missing host"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_invalid_base64() {
+        assert!(CloudId::parse("my-deployment:not base64!").is_err());
+    }
+}
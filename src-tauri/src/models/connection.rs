@@ -1,14 +1,222 @@
+use crate::models::cloud_id::CloudId;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ConnectionProfile {
     pub id: String,
     pub name: String,
     pub url: String,
-    pub auth_type: String, // "basic" or "iam"
+    // Further seed URLs of the same cluster, tried round-robin with `url`
+    #[serde(default)]
+    pub urls: Vec<String>,
+    // Discover node addresses from `_nodes/http`
+    #[serde(default)]
+    pub sniff: bool,
+    // Elastic Cloud ID; when set it determines the URL
+    pub cloud_id: Option<String>,
+    pub auth_type: String, // "basic", "iam", "apikey" or "oauth2"
     pub username: Option<String>,
     pub password: Option<String>,
     pub region: Option<String>,
     pub access_key: Option<String>,
     pub secret_key: Option<String>,
+    // IAM credential source: "static" (default), "env" or "profile" (~/.aws named profile)
+    pub credential_source: Option<String>,
+    pub aws_profile: Option<String>,
+    pub session_token: Option<String>,
+    // Optional role assumed on top of the base credentials
+    pub role_arn: Option<String>,
+    pub role_session_name: Option<String>,
+    pub external_id: Option<String>,
+    pub sts_endpoint: Option<String>,
+    // SigV4 service name ("es" or "aoss"); detected from the endpoint host when unset
+    pub aws_service: Option<String>,
+    #[serde(default)]
+    pub unsigned_payload: bool,
+    // Local command printing secrets as JSON; its secrets are never stored
+    pub credential_helper: Option<String>,
+    // Seconds the helper output is cached (default 300, 0 disables caching)
+    pub credential_helper_ttl: Option<u64>,
+    // API key auth: either `api_key_id` + `api_key`, or an already encoded `api_key`
+    pub api_key_id: Option<String>,
+    pub api_key: Option<String>,
+    // OAuth2 client-credentials auth
+    pub token_url: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub scope: Option<String>,
+    // TLS: PEM CA bundle, client certificate as PEM (+ key) or PKCS#12 (.p12/.pfx)
+    pub ca_cert_path: Option<String>,
+    pub client_cert_path: Option<String>,
+    pub client_key_path: Option<String>,
+    pub client_cert_password: Option<String>,
+    // SHA-256 of the server certificate, hex with or without colons
+    pub tls_fingerprint: Option<String>,
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    // SSH tunnel: the URL is reached through a local port forward via this host.
+    // Without a key file the SSH agent is used.
+    pub ssh_host: Option<String>,
+    pub ssh_port: Option<u16>,
+    pub ssh_user: Option<String>,
+    pub ssh_key_path: Option<String>,
+    // Bastions passed to `ssh -J`, as `[user@]host[:port]`, in hop order
+    #[serde(default)]
+    pub ssh_jump_hosts: Vec<String>,
+    // Proxy as http://, https://, socks5:// or socks5h:// URL; ignored when tunnelling
+    pub proxy_url: Option<String>,
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
+    // Comma-separated hosts, domains or CIDRs that bypass the proxy
+    pub no_proxy: Option<String>,
+    // Sent with every request, in order
+    #[serde(default)]
+    pub headers: Vec<CustomHeader>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default)]
+    pub compression: CompressionSettings,
+    // Defaults are 10s to connect and 60s per request; a request timeout of 0 disables it
+    pub connect_timeout_ms: Option<u64>,
+    pub request_timeout_ms: Option<u64>,
+    // Requests sent to the cluster at once, queued beyond that; defaults to 4
+    pub max_concurrent_requests: Option<u32>,
+    // Responses larger than this are aborted; defaults to 512 MiB
+    pub max_response_bytes: Option<u64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Set for profiles loaded from a shared ruskview.toml
+    #[serde(default)]
+    pub read_only: bool,
+    // File the profile was loaded from, if any
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomHeader {
+    pub name: String,
+    pub value: String,
+    // Secret values are encrypted at rest and left out of exports without secrets
+    #[serde(default)]
+    pub secret: bool,
+}
+
+/// When and how often failed requests are retried.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    // Total attempts including the first; 1 disables retries
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    // Caps the backoff, and a longer `Retry-After` is not waited for
+    pub max_backoff_ms: u64,
+    pub retry_on_status: Vec<u16>,
+    // Connection failures and resets
+    pub retry_on_transport_errors: bool,
+    // Requests that timed out may still be running on the cluster, so
+    // sending them again can pile more load onto an overloaded one
+    pub retry_on_timeouts: bool,
+    // Also retry writes such as POST to `_bulk`, which may apply twice
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 200,
+            max_backoff_ms: 10_000,
+            retry_on_status: vec![429, 502, 503, 504],
+            retry_on_transport_errors: true,
+            retry_on_timeouts: false,
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+/// Compression of responses and request bodies.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionSettings {
+    // Advertise gzip and deflate and decode compressed responses
+    pub responses: bool,
+    // Gzip request bodies of at least `min_request_bytes`, e.g. bulk imports
+    pub requests: bool,
+    pub min_request_bytes: usize,
+}
+
+impl Default for CompressionSettings {
+    fn default() -> Self {
+        Self {
+            responses: true,
+            requests: false,
+            min_request_bytes: 8 * 1024,
+        }
+    }
+}
+
+impl ConnectionProfile {
+    // The Cloud ID's endpoint when one is set, otherwise `url`
+    pub fn resolved_url(&self) -> Result<String, String> {
+        match self.cloud_id.as_deref().filter(|c| !c.is_empty()) {
+            Some(cloud_id) => Ok(CloudId::parse(cloud_id)?.url),
+            None => Ok(self.url.clone()),
+        }
+    }
+
+    // Copy with every secret removed, e.g. for sharing
+    pub fn without_secrets(&self) -> Self {
+        Self {
+            password: None,
+            secret_key: None,
+            api_key: None,
+            client_secret: None,
+            client_cert_password: None,
+            session_token: None,
+            proxy_password: None,
+            headers: self
+                .headers
+                .iter()
+                .map(|h| CustomHeader {
+                    value: if h.secret {
+                        String::new()
+                    } else {
+                        h.value.clone()
+                    },
+                    ..h.clone()
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    // Takes each secret this profile lacks from `other`, e.g. when an
+    // export without secrets overwrites a stored profile
+    pub fn keep_secrets_from(&mut self, other: &ConnectionProfile) {
+        fn keep(secret: &mut Option<String>, other: &Option<String>) {
+            if secret.as_deref().is_none_or(str::is_empty) {
+                secret.clone_from(other);
+            }
+        }
+        keep(&mut self.password, &other.password);
+        keep(&mut self.secret_key, &other.secret_key);
+        keep(&mut self.api_key, &other.api_key);
+        keep(&mut self.client_secret, &other.client_secret);
+        keep(&mut self.client_cert_password, &other.client_cert_password);
+        keep(&mut self.session_token, &other.session_token);
+        keep(&mut self.proxy_password, &other.proxy_password);
+        for header in self
+            .headers
+            .iter_mut()
+            .filter(|h| h.secret && h.value.is_empty())
+        {
+            if let Some(stored) = other
+                .headers
+                .iter()
+                .find(|h| h.secret && h.name.eq_ignore_ascii_case(&header.name))
+            {
+                header.value.clone_from(&stored.value);
+            }
+        }
+    }
 }
@@ -1,36 +1,227 @@
+use crate::auth::helper::CredentialHelper;
 use crate::db::Database;
+use crate::error::{AppError, ErrorKind};
+use crate::es::cancel::InFlightRequest;
 use crate::es::client::EsClient;
+use crate::es::http_pool::HttpPool;
+use crate::es::scheduler::Scheduler;
+use crate::health::SessionHealth;
 use crate::models::connection::ConnectionProfile;
+use crate::team_config::TeamConfig;
+use serde::Serialize;
 use serde_json::Value;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
-use tokio::sync::{RwLock, mpsc, oneshot};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
 
 pub struct AppState {
-    pub current_profile: RwLock<Option<ConnectionProfile>>,
-    pub active_client: RwLock<Option<Arc<dyn EsClient>>>,
-    pub request_queue: mpsc::Sender<RequestJob>,
-    pub connection_cache: RwLock<HashMap<String, Instant>>,
+    pub sessions: RwLock<Sessions>,
+    // Every cluster request goes through here
+    pub scheduler: Arc<Scheduler>,
+    // HTTP clients and connections shared by all ES clients
+    pub http_pool: Arc<HttpPool>,
     pub db: Mutex<Option<Database>>,
+    pub credential_helper: CredentialHelper,
+    pub last_activity: Mutex<Instant>,
+    pub team_config: TeamConfig,
+    // Console requests by request id, until they finish or are cancelled
+    pub in_flight: tokio::sync::Mutex<HashMap<String, InFlightRequest>>,
 }
 
-pub struct RequestJob {
-    pub method: String,
-    pub path: String,
-    pub body: Option<Value>,
-    pub profile_override: Option<ConnectionProfile>,
-    pub responder: oneshot::Sender<Result<Value, String>>,
+/// An open connection to a cluster, until `disconnect` or a store lock.
+pub struct Session {
+    pub info: SessionInfo,
+    // As connected, before tunnel or Cloud ID rewrites
+    pub profile: ConnectionProfile,
+    pub client: Arc<dyn EsClient>,
+    // Kept up to date by the session's health watcher
+    health: Mutex<SessionHealth>,
+}
+
+impl Session {
+    pub fn new(info: SessionInfo, profile: ConnectionProfile, client: Arc<dyn EsClient>) -> Self {
+        Self {
+            health: Mutex::new(info.health.clone()),
+            info,
+            profile,
+            client,
+        }
+    }
+
+    /// The session info with its current health.
+    pub fn info(&self) -> SessionInfo {
+        SessionInfo {
+            health: self.health(),
+            ..self.info.clone()
+        }
+    }
+
+    pub fn health(&self) -> SessionHealth {
+        self.health
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_health(&self, health: SessionHealth) {
+        *self.health.lock().unwrap_or_else(PoisonError::into_inner) = health;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    // The profile id, or a generated one for unsaved profiles
+    pub session_id: String,
+    pub name: String,
+    pub url: String,
+    // Unix time in milliseconds
+    pub connected_at: u64,
+    // `GET /` at connect time
+    pub cluster: Value,
+    pub health: SessionHealth,
+}
+
+#[derive(Default)]
+pub struct Sessions {
+    by_id: HashMap<String, Arc<Session>>,
+    // Used by commands that do not name a session: the latest connected
+    default: Option<String>,
+}
+
+impl Sessions {
+    /// The named session, or the default one.
+    pub fn get(&self, id: Option<&str>) -> Result<Arc<Session>, AppError> {
+        let Some(id) = id.or(self.default.as_deref()) else {
+            return Err(AppError::not_connected());
+        };
+        self.by_id
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AppError::new(ErrorKind::NotConnected, format!("No session {}", id)))
+    }
+
+    // Replaces any session for the same connection; the old client closes
+    // once its last in-flight request is done
+    pub fn insert(&mut self, session: Session) -> Arc<Session> {
+        let id = session.info.session_id.clone();
+        let session = Arc::new(session);
+        self.by_id.insert(id.clone(), session.clone());
+        self.default = Some(id);
+        session
+    }
+
+    /// False once the session is closed or replaced by a new connect.
+    pub fn is_current(&self, session: &Arc<Session>) -> bool {
+        self.by_id
+            .get(&session.info.session_id)
+            .is_some_and(|current| Arc::ptr_eq(current, session))
+    }
+
+    /// Swaps in a new client for a reconnected session, unless the session
+    /// is no longer current.
+    pub fn replace(
+        &mut self,
+        session: &Arc<Session>,
+        client: Arc<dyn EsClient>,
+    ) -> Option<Arc<Session>> {
+        if !self.is_current(session) {
+            return None;
+        }
+        let reconnected = Arc::new(Session::new(
+            session.info(),
+            session.profile.clone(),
+            client,
+        ));
+        self.by_id
+            .insert(session.info.session_id.clone(), reconnected.clone());
+        Some(reconnected)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Arc<Session>> {
+        let session = self.by_id.remove(id)?;
+        if self.default.as_deref() == Some(id) {
+            self.default = self
+                .by_id
+                .values()
+                .max_by_key(|s| s.info.connected_at)
+                .map(|s| s.info.session_id.clone());
+        }
+        Some(session)
+    }
+
+    pub fn list(&self) -> Vec<SessionInfo> {
+        let mut sessions: Vec<SessionInfo> = self.by_id.values().map(|s| s.info()).collect();
+        sessions.sort_by_key(|s| s.connected_at);
+        sessions
+    }
 }
 
 impl AppState {
-    pub fn new(request_queue: mpsc::Sender<RequestJob>, db: Option<Database>) -> Self {
+    pub fn new(db: Option<Database>) -> Self {
+        let pool_config = db
+            .as_ref()
+            .and_then(|db| db.pool_config().ok())
+            .unwrap_or_default();
         Self {
-            current_profile: RwLock::new(None),
-            active_client: RwLock::new(None),
-            request_queue,
-            connection_cache: RwLock::new(HashMap::new()),
+            sessions: RwLock::new(Sessions::default()),
+            scheduler: Arc::new(Scheduler::default()),
+            http_pool: Arc::new(HttpPool::new(pool_config)),
             db: Mutex::new(db),
+            credential_helper: CredentialHelper::default(),
+            last_activity: Mutex::new(Instant::now()),
+            team_config: TeamConfig::default(),
+            in_flight: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Records user activity for the idle lock
+    pub fn touch(&self) {
+        if let Ok(mut last) = self.last_activity.lock() {
+            *last = Instant::now();
+        }
+    }
+
+    // True when a master password is set, the store is unlocked and the
+    // configured idle timeout (0 disables it) has passed.
+    pub fn idle_lock_due(&self) -> bool {
+        let idle_for = self
+            .last_activity
+            .lock()
+            .map(|last| last.elapsed())
+            .unwrap_or_default();
+        let Ok(db_guard) = self.db.lock() else {
+            return false;
+        };
+        let Some(db) = db_guard.as_ref() else {
+            return false;
+        };
+        if db.is_locked() || !db.has_master_password().unwrap_or(false) {
+            return false;
+        }
+        db.idle_timeout()
+            .is_ok_and(|timeout| timeout > Duration::ZERO && idle_for >= timeout)
+    }
+
+    // Locks the profile store and drops everything holding decrypted secrets.
+    // Without a master password there is nothing to lock the store with.
+    pub async fn lock_store(&self) -> Result<(), String> {
+        {
+            let mut db_guard = self.db.lock().map_err(|_| "Failed to lock db")?;
+            let db = db_guard.as_mut().ok_or("Database not initialized")?;
+            if !db.has_master_password().map_err(|e| e.to_string())? {
+                return Err("Set a master password before locking the store".to_string());
+            }
+            db.lock().map_err(|e| e.to_string())?;
+        }
+
+        *self.sessions.write().await = Sessions::default();
+        // Pending requests hold their session's client until they finish
+        for (_, request) in self.in_flight.lock().await.drain() {
+            let _ = request.cancel.send(());
         }
+        self.http_pool.clear();
+        self.credential_helper.clear().await;
+        Ok(())
     }
 }
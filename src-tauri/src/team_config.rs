@@ -0,0 +1,354 @@
+use crate::models::connection::ConnectionProfile;
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+// Ids of file profiles are prefixed so they never clash with stored ones
+pub const ID_PREFIX: &str = "file:";
+// A shared file must not carry these as literals
+const SECRET_FIELDS: &[&str] = &[
+    "password",
+    "secret_key",
+    "session_token",
+    "api_key",
+    "client_secret",
+    "client_cert_password",
+    "proxy_password",
+];
+
+/// Read-only profiles loaded from a shared `ruskview.toml`:
+///
+/// ```toml
+/// [[clusters]]
+/// name = "Prod logs"
+/// url = "https://logs.example.com:9200"
+/// auth_type = "basic"
+/// username = "reader"
+/// password = "${PROD_LOGS_PASSWORD}"
+/// tags = ["prod"]
+/// ```
+///
+/// Any string may reference environment variables as `${NAME}`. Secrets,
+/// including secret header values, must be a single `${NAME}` reference or
+/// come from a `credential_helper` command; clusters with literal ones are
+/// skipped, as are clusters repeating an earlier id.
+#[derive(Default)]
+pub struct TeamConfig {
+    inner: RwLock<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    profiles: Vec<ConnectionProfile>,
+}
+
+impl TeamConfig {
+    pub async fn set_path(&self, path: Option<PathBuf>) -> Result<()> {
+        {
+            let mut inner = self.inner.write().await;
+            inner.path = path;
+            inner.modified = None;
+            inner.profiles.clear();
+        }
+        self.reload_if_changed().await.map(|_| ())
+    }
+
+    /// Reloads the file when its modification time changed.
+    /// Returns true when the profile list was replaced.
+    pub async fn reload_if_changed(&self) -> Result<bool> {
+        let mut inner = self.inner.write().await;
+        let Some(path) = inner.path.clone() else {
+            return Ok(false);
+        };
+
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        if modified == inner.modified {
+            return Ok(false);
+        }
+        inner.modified = modified;
+
+        if modified.is_none() {
+            // The file went away; drop its profiles
+            let had_profiles = !inner.profiles.is_empty();
+            inner.profiles.clear();
+            return Ok(had_profiles);
+        }
+
+        // On a parse error the previous profiles stay in place
+        inner.profiles = load(&path)?;
+        info!(
+            "Loaded {} profiles from {}",
+            inner.profiles.len(),
+            path.display()
+        );
+        Ok(true)
+    }
+
+    pub async fn profiles(&self) -> Vec<ConnectionProfile> {
+        self.inner.read().await.profiles.clone()
+    }
+}
+
+pub fn is_file_profile(id: &str) -> bool {
+    id.starts_with(ID_PREFIX)
+}
+
+fn load(path: &Path) -> Result<Vec<ConnectionProfile>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let document: toml::Table =
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let clusters = match document.get("clusters") {
+        Some(toml::Value::Array(clusters)) => clusters.clone(),
+        Some(_) => return Err(anyhow!("`clusters` must be an array of tables")),
+        None => Vec::new(),
+    };
+
+    let source = path.display().to_string();
+    let env = |name: &str| std::env::var(name).ok();
+    let mut profiles: Vec<ConnectionProfile> = Vec::new();
+    for (index, cluster) in clusters.into_iter().enumerate() {
+        // A bad entry (e.g. an unset variable) only drops that cluster
+        match parse_cluster(cluster, &env) {
+            Ok(mut profile) => {
+                profile.id = format!("{}{}", ID_PREFIX, profile.id);
+                if profiles.iter().any(|p| p.id == profile.id) {
+                    warn!(
+                        "Skipping cluster #{} in {}: id {} is already used",
+                        index + 1,
+                        source,
+                        profile.id
+                    );
+                    continue;
+                }
+                profile.read_only = true;
+                profile.source = Some(source.clone());
+                profiles.push(profile);
+            }
+            Err(e) => warn!("Skipping cluster #{} in {}: {}", index + 1, source, e),
+        }
+    }
+    Ok(profiles)
+}
+
+// Looks up environment variables; tests pass their own
+type Env<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn parse_cluster(cluster: toml::Value, env: Env) -> Result<ConnectionProfile> {
+    let toml::Value::Table(table) = cluster else {
+        return Err(anyhow!("cluster entries must be tables"));
+    };
+    check_secrets(&table)?;
+    let mut table = expand_table(table, env)?;
+
+    // Without an explicit id the name doubles as one
+    if !table.contains_key("id") {
+        let name = table
+            .get("name")
+            .and_then(|n| n.as_str())
+            .ok_or_else(|| anyhow!("missing `name`"))?
+            .to_string();
+        table.insert("id".to_string(), toml::Value::String(name));
+    }
+
+    Ok(toml::Value::Table(table).try_into()?)
+}
+
+// Secrets have to be a whole `${NAME}` reference, so the file can be shared
+fn check_secrets(table: &toml::Table) -> Result<()> {
+    let is_literal = |value: &toml::Value| {
+        value.as_str().is_some_and(|s| {
+            let name = s.strip_prefix("${").and_then(|rest| rest.strip_suffix('}'));
+            !s.is_empty() && name.is_none_or(|name| name.is_empty() || name.contains('}'))
+        })
+    };
+    for field in SECRET_FIELDS {
+        if table.get(*field).is_some_and(is_literal) {
+            return Err(anyhow!("`{}` must be a ${{NAME}} reference", field));
+        }
+    }
+    let headers = table.get("headers").and_then(|h| h.as_array());
+    for header in headers.into_iter().flatten() {
+        let is_secret = header.get("secret").and_then(|s| s.as_bool()) == Some(true);
+        if is_secret && header.get("value").is_some_and(is_literal) {
+            return Err(anyhow!("secret header values must be ${{NAME}} references"));
+        }
+    }
+    Ok(())
+}
+
+fn expand_value(value: toml::Value, env: Env) -> Result<toml::Value> {
+    Ok(match value {
+        toml::Value::String(s) => toml::Value::String(expand_env(&s, env)?),
+        toml::Value::Array(items) => toml::Value::Array(
+            items
+                .into_iter()
+                .map(|v| expand_value(v, env))
+                .collect::<Result<_>>()?,
+        ),
+        toml::Value::Table(table) => toml::Value::Table(expand_table(table, env)?),
+        other => other,
+    })
+}
+
+fn expand_table(table: toml::Table, env: Env) -> Result<toml::Table> {
+    table
+        .into_iter()
+        .map(|(k, v)| Ok((k, expand_value(v, env)?)))
+        .collect()
+}
+
+// Replaces every `${NAME}` with the value of the environment variable
+fn expand_env(value: &str, env: Env) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| anyhow!("unterminated `${{` reference"))?;
+        let name = &after[..end];
+        let var = env(name).ok_or_else(|| anyhow!("environment variable {} is not set", name))?;
+        expanded.push_str(&var);
+        rest = &after[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(name: &str) -> Option<String> {
+        match name {
+            "ES_PASSWORD" => Some("s3cret".to_string()),
+            "ES_HOST" => Some("logs.example.com".to_string()),
+            _ => None,
+        }
+    }
+
+    fn cluster(toml: &str) -> Result<ConnectionProfile> {
+        parse_cluster(toml::Value::Table(toml::from_str(toml).unwrap()), &env)
+    }
+
+    #[test]
+    fn expands_references() {
+        assert_eq!(
+            expand_env("https://${ES_HOST}:9200", &env).unwrap(),
+            "https://logs.example.com:9200"
+        );
+        assert_eq!(
+            expand_env("${ES_HOST}${ES_HOST}", &env).unwrap(),
+            "logs.example.comlogs.example.com"
+        );
+        assert_eq!(expand_env("no references", &env).unwrap(), "no references");
+    }
+
+    #[test]
+    fn rejects_missing_and_unterminated_references() {
+        let err = expand_env("${UNSET_VAR}", &env).unwrap_err();
+        assert_eq!(err.to_string(), "environment variable UNSET_VAR is not set");
+        assert!(expand_env("${ES_HOST", &env).is_err());
+    }
+
+    #[test]
+    fn parses_cluster_with_secret_reference() {
+        let profile = cluster(
+            r#"
+            name = "Prod logs"
+            url = "https://${ES_HOST}:9200"
+            auth_type = "basic"
+            username = "reader"
+            password = "${ES_PASSWORD}"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profile.id, "Prod logs");
+        assert_eq!(profile.url, "https://logs.example.com:9200");
+        assert_eq!(profile.password.as_deref(), Some("s3cret"));
+    }
+
+    #[test]
+    fn rejects_literal_secrets() {
+        for field in ["password", "api_key", "secret_key", "client_secret"] {
+            let err = cluster(&format!(
+                r#"
+                name = "Prod"
+                url = "https://localhost:9200"
+                auth_type = "basic"
+                {} = "hunter2"
+                "#,
+                field
+            ))
+            .unwrap_err();
+            assert!(
+                err.to_string().starts_with(&format!("`{}`", field)),
+                "{}",
+                err
+            );
+        }
+        // Half a reference is still a literal
+        assert!(
+            cluster(
+                r#"
+                name = "Prod"
+                url = "https://localhost:9200"
+                auth_type = "basic"
+                password = "pre${ES_PASSWORD}"
+                "#
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_literal_secret_headers() {
+        let err = cluster(
+            r#"
+            name = "Prod"
+            url = "https://localhost:9200"
+            auth_type = "none"
+            headers = [
+                { name = "X-Tenant", value = "logs" },
+                { name = "X-Token", value = "abc", secret = true },
+            ]
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("secret header"), "{}", err);
+    }
+
+    #[test]
+    fn skips_duplicate_ids() {
+        let path = std::env::temp_dir().join(format!("ruskview-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"
+            [[clusters]]
+            name = "Prod"
+            url = "https://one:9200"
+            auth_type = "none"
+
+            [[clusters]]
+            name = "Prod"
+            url = "https://two:9200"
+            auth_type = "none"
+            "#,
+        )
+        .unwrap();
+        let profiles = load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let profiles = profiles.unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].id, "file:Prod");
+        assert_eq!(profiles[0].url, "https://one:9200");
+    }
+}
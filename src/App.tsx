@@ -4,10 +4,12 @@ import { Login } from './views/Login';
 import { Dashboard } from './views/Dashboard';
 import { SettingsModal } from './components/SettingsModal';
 import { ToastContainer } from './components/ToastContainer';
+import { useToastStore } from './stores/useToastStore';
 import { listen } from '@tauri-apps/api/event';
 
 function App() {
-  const { isAuthenticated, theme, setTheme } = useAppStore();
+  const { isAuthenticated, theme, setTheme, logout } = useAppStore();
+  const addToast = useToastStore((state) => state.addToast);
   const [isSettingsOpen, setIsSettingsOpen] = useState(false);
 
   useEffect(() => {
@@ -32,8 +34,19 @@ function App() {
       setIsSettingsOpen(true);
     });
 
+    // Locking the store closes every session, so go back to Login
+    const unlistenLock = listen('store-locked', () => {
+      logout();
+      addToast({
+        type: 'info',
+        title: 'Profile Store Locked',
+        message: 'Locked after inactivity. Enter the master password to reconnect.',
+      });
+    });
+
     return () => {
       unlisten.then((f) => f());
+      unlistenLock.then((f) => f());
     };
   }, []);
 
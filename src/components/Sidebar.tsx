@@ -1,3 +1,5 @@
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { useAppStore } from '../stores/useAppStore';
 import {
   LayoutDashboard,
@@ -15,7 +17,20 @@ import {
   ChevronDown
 } from 'lucide-react';
 import clsx from 'clsx';
-import { useState } from 'react';
+import { useEffect, useState } from 'react';
+
+type SessionHealth = {
+  status: 'connected' | 'degraded' | 'unreachable' | 'auth_expired';
+  color: string | null;
+  error: string | null;
+};
+
+const STATUS_LABELS: Record<SessionHealth['status'], string> = {
+  connected: 'Connected',
+  degraded: 'Degraded',
+  unreachable: 'Reconnecting...',
+  auth_expired: 'Credentials expired',
+};
 
 type NavItem = {
   id: string;
@@ -25,13 +40,37 @@ type NavItem = {
 };
 
 export function Sidebar() {
-  const { currentView, setView, logout } = useAppStore();
+  const { currentView, setView, logout, currentProfile } = useAppStore();
   const [expanded, setExpanded] = useState<Record<string, boolean>>({
     operations: true,
     cluster: true,
     security: false,
   });
 
+  const [health, setHealth] = useState<SessionHealth | null>(null);
+
+  // Pushed by the backend health watcher on every status or color change
+  useEffect(() => {
+    const unlisten = listen<{ session_id: string; health: SessionHealth }>(
+      'session-health',
+      (event) => {
+        if (event.payload.session_id === currentProfile?.sessionId) {
+          setHealth(event.payload.health);
+        }
+      }
+    );
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, [currentProfile?.sessionId]);
+
+  const handleDisconnect = async () => {
+    if (currentProfile?.sessionId) {
+      await invoke('disconnect', { sessionId: currentProfile.sessionId }).catch(console.error);
+    }
+    logout();
+  };
+
   const toggleExpand = (id: string) => {
     setExpanded((prev) => ({ ...prev, [id]: !prev[id] }));
   };
@@ -131,8 +170,24 @@ export function Sidebar() {
         ))}
       </nav>
       <div className="border-t border-macos-border p-4 bg-black/5 dark:bg-white/5">
+        {health && (
+          <div
+            className="mb-2 flex items-center gap-2 px-2 text-xs text-macos-textSecondary"
+            title={health.error ?? undefined}
+          >
+            <span
+              className={clsx('h-2 w-2 rounded-full', {
+                'bg-green-500': health.color === 'green',
+                'bg-yellow-500': health.color === 'yellow',
+                'bg-macos-red': health.color === 'red',
+                'bg-gray-400': !health.color,
+              })}
+            />
+            {STATUS_LABELS[health.status]}
+          </div>
+        )}
         <button
-          onClick={logout}
+          onClick={handleDisconnect}
           className="flex w-full items-center gap-2 rounded-md px-2 py-1.5 text-sm font-medium text-macos-red hover:bg-macos-red/10 transition-colors"
         >
           <LogOut className="h-4 w-4" />
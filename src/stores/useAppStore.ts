@@ -7,6 +7,7 @@ interface AuthProfile {
   authType: 'basic' | 'iam';
   username?: string;
   region?: string; // For IAM
+  sessionId?: string; // Backend session opened by connect_to_cluster
 }
 
 export type Theme = 'light' | 'dark' | 'system';
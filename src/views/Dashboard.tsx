@@ -1,7 +1,7 @@
-import { useEffect, useState } from 'react';
+import { useEffect, useRef, useState } from 'react';
 import { useAppStore } from '../stores/useAppStore';
 import { Sidebar } from '../components/Sidebar';
-import { invoke } from '@tauri-apps/api/core';
+import { Channel, invoke } from '@tauri-apps/api/core';
 import {
   Activity,
   CheckCircle2,
@@ -77,6 +77,7 @@ export function Dashboard() {
 
 function ClusterOverview() {
   const currentCluster = useAppStore((state) => state.currentCluster);
+  const sessionId = useAppStore((state) => state.currentProfile?.sessionId);
   const [health, setHealth] = useState<ClusterHealth | null>(null);
   const [stats, setStats] = useState<ClusterStats | null>(null);
   const [loading, setLoading] = useState(true);
@@ -89,12 +90,16 @@ function ClusterOverview() {
         const healthRes = await invoke<ClusterHealth>('perform_cluster_op', {
           operation: 'health',
           params: {},
+          priority: 'background',
+          sessionId,
         });
         setHealth(healthRes);
 
         const statsRes = await invoke<ClusterStats>('perform_cluster_op', {
           operation: 'stats',
           params: {},
+          priority: 'background',
+          sessionId,
         });
         console.log('Stats:', statsRes);
         setStats(statsRes);
@@ -106,7 +111,7 @@ function ClusterOverview() {
     };
 
     fetchData();
-  }, [currentCluster]);
+  }, [currentCluster, sessionId]);
 
   if (loading) {
     return (
@@ -343,18 +348,66 @@ function Pod({
   );
 }
 
+type ResponseBody =
+  | { type: 'empty' }
+  | { type: 'json'; value: unknown }
+  | { type: 'ndjson'; value: unknown[] }
+  | { type: 'text'; value: string };
+
+interface EsResponse {
+  status: number;
+  headers: Record<string, string>;
+  elapsed_ms: number;
+  size: number;
+  content_type: string | null;
+  body: ResponseBody;
+}
+
+interface StreamProgress {
+  received: number;
+  expected: number | null;
+}
+
+function formatBytes(bytes: number): string {
+  if (bytes < 1024 * 1024) {
+    return `${(bytes / 1024).toFixed(1)} KB`;
+  }
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+}
+
+function formatBody(text: string, contentType: string | null): string {
+  if (!contentType?.includes('json') || contentType.includes('ndjson')) {
+    return text;
+  }
+  try {
+    return JSON.stringify(JSON.parse(text), null, 2);
+  } catch {
+    return text;
+  }
+}
+
 function SearchView() {
+  const sessionId = useAppStore((state) => state.currentProfile?.sessionId);
   const [method, setMethod] = useState('GET');
   const [path, setPath] = useState('/_search');
   const [body, setBody] = useState('{\n  "query": {\n    "match_all": {}\n  }\n}');
   const [response, setResponse] = useState<string | null>(null);
+  const [responseMeta, setResponseMeta] = useState<string | null>(null);
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const requestId = useRef<string | null>(null);
+
+  const handleCancel = () => {
+    if (requestId.current) {
+      invoke('cancel_request', { requestId: requestId.current }).catch(console.error);
+    }
+  };
 
   const handleRun = async () => {
     setLoading(true);
     setError(null);
     setResponse(null);
+    setResponseMeta(null);
     try {
       let parsedBody = null;
       if (body.trim() && method !== 'GET' && method !== 'DELETE') {
@@ -367,15 +420,50 @@ function SearchView() {
         }
       }
 
-      const res = await invoke('proxy_request', {
+      // The body arrives in binary pieces, each followed by a progress update
+      const pieces: Uint8Array[] = [];
+      let receivedBytes = 0;
+      let waitingFor: { size: number; resolve: () => void } | null = null;
+      const onChunk = new Channel<ArrayBuffer | StreamProgress>();
+      onChunk.onmessage = (message) => {
+        if (message instanceof ArrayBuffer) {
+          pieces.push(new Uint8Array(message));
+          receivedBytes += message.byteLength;
+          if (waitingFor && receivedBytes >= waitingFor.size) {
+            waitingFor.resolve();
+          }
+        } else {
+          const { received, expected } = message;
+          setResponseMeta(
+            expected
+              ? `${formatBytes(received)} of ${formatBytes(expected)}`
+              : `${formatBytes(received)} received`,
+          );
+        }
+      };
+
+      requestId.current = crypto.randomUUID();
+      const meta = await invoke<EsResponse>('proxy_request_stream', {
         method,
         path,
         body: parsedBody,
+        requestId: requestId.current,
+        onChunk,
+        sessionId,
       });
-      setResponse(JSON.stringify(res, null, 2));
+      // The command can resolve before the last pieces are delivered
+      if (receivedBytes < meta.size) {
+        await new Promise<void>((resolve) => {
+          waitingFor = { size: meta.size, resolve };
+        });
+      }
+      const text = await new Blob(pieces).text();
+      setResponseMeta(`${meta.status} · ${meta.elapsed_ms} ms · ${meta.size} bytes`);
+      setResponse(formatBody(text, meta.content_type) || '(empty body)');
     } catch (err: any) {
-      setError(typeof err === 'string' ? err : JSON.stringify(err));
+      setError(typeof err === 'string' ? err : err?.message ?? JSON.stringify(err));
     } finally {
+      requestId.current = null;
       setLoading(false);
     }
   };
@@ -425,6 +513,14 @@ function SearchView() {
           )}
           Run
         </button>
+        {loading && (
+          <button
+            onClick={handleCancel}
+            className="rounded-lg border border-macos-border px-4 py-2 text-sm text-macos-text shadow-sm hover:bg-macos-surface"
+          >
+            Cancel
+          </button>
+        )}
       </div>
 
       <div className="grid grid-cols-1 md:grid-cols-2 gap-4 flex-1 min-h-0">
@@ -446,6 +542,7 @@ function SearchView() {
           <div className="bg-macos-surface border-b border-macos-border px-4 py-2 text-xs font-medium text-macos-textSecondary flex justify-between">
             <span>Response</span>
             {error && <span className="text-macos-red">Error</span>}
+            {!error && responseMeta && <span>{responseMeta}</span>}
           </div>
           <div className="flex-1 overflow-auto p-4">
             {error ? (
@@ -477,7 +574,18 @@ interface CatIndex {
   'store.size': string;
 }
 
+// `proxy_request_raw` answers with the envelope as one line of JSON, a
+// newline, then the response body exactly as received
+async function invokeRaw(args: Record<string, unknown>) {
+  const payload = new Uint8Array(await invoke<ArrayBuffer>('proxy_request_raw', args));
+  const newline = payload.indexOf(10);
+  const decoder = new TextDecoder();
+  const envelope = JSON.parse(decoder.decode(payload.subarray(0, newline))) as EsResponse;
+  return { envelope, body: decoder.decode(payload.subarray(newline + 1)) };
+}
+
 function IndicesView() {
+  const sessionId = useAppStore((state) => state.currentProfile?.sessionId);
   const [indices, setIndices] = useState<CatIndex[]>([]);
   const [loading, setLoading] = useState(true);
 
@@ -485,13 +593,17 @@ function IndicesView() {
     const fetchIndices = async () => {
       setLoading(true);
       try {
-        const res = await invoke<CatIndex[]>('proxy_request', {
+        // Large clusters list thousands of indices, so skip the parse in Rust
+        const { body } = await invokeRaw({
           method: 'GET',
           path: '/_cat/indices?format=json',
           body: null,
+          sessionId,
         });
+        const parsed: unknown = body ? JSON.parse(body) : [];
+        const rows = Array.isArray(parsed) ? (parsed as CatIndex[]) : [];
         // Sort by name by default
-        const sorted = Array.isArray(res) ? res.sort((a, b) => a.index.localeCompare(b.index)) : [];
+        const sorted = rows.sort((a, b) => a.index.localeCompare(b.index));
         setIndices(sorted);
       } catch (error) {
         console.error('Failed to fetch indices:', error);
@@ -501,7 +613,7 @@ function IndicesView() {
     };
 
     fetchIndices();
-  }, []);
+  }, [sessionId]);
 
   if (loading) {
     return (
@@ -4,6 +4,15 @@ import { useToastStore } from '../stores/useToastStore';
 import { Shield, Key, Server, Save } from 'lucide-react';
 import clsx from 'clsx';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+// Commands reject with an AppError `{ kind, message, status, ... }`
+function describeError(error: any): string {
+  if (typeof error === 'string') return error;
+  const message = error?.message ?? JSON.stringify(error);
+  const details = [error?.kind, error?.status].filter((d) => d != null).join(' ');
+  return details ? `${message} (${details})` : message;
+}
 
 export function Login() {
   const login = useAppStore((state) => state.login);
@@ -19,20 +28,53 @@ export function Login() {
   const [profileName, setProfileName] = useState('Local Cluster');
   const [isLoading, setIsLoading] = useState(false);
   const [savedProfiles, setSavedProfiles] = useState<any[]>([]);
+  const [selectedProfile, setSelectedProfile] = useState<any | null>(null);
+  const [storeLocked, setStoreLocked] = useState(false);
+  const [masterPassword, setMasterPassword] = useState('');
+
+  // Saved profiles stay hidden until a locked store is unlocked
+  const loadProfiles = async () => {
+    try {
+      const status = await invoke<{ locked: boolean }>('get_store_status');
+      setStoreLocked(status.locked);
+      setSavedProfiles(status.locked ? [] : await invoke<any[]>('get_profiles'));
+    } catch (error) {
+      console.error(error);
+    }
+  };
 
   useEffect(() => {
-    invoke('get_profiles')
-      .then((profiles: any) => {
-        setSavedProfiles(profiles);
-      })
-      .catch(console.error);
+    loadProfiles();
+    const unlisten = listen('store-locked', () => {
+      loadProfiles();
+    });
+
+    return () => {
+      unlisten.then((f) => f());
+    };
   }, []);
 
+  const handleUnlock = async () => {
+    try {
+      await invoke('unlock_store', { password: masterPassword });
+      setMasterPassword('');
+      await loadProfiles();
+    } catch (error) {
+      addToast({
+        type: 'error',
+        title: 'Unlock Failed',
+        message: describeError(error),
+        duration: 5000,
+      });
+    }
+  };
+
   const handleProfileSelect = (e: React.ChangeEvent<HTMLSelectElement>) => {
     const profileId = e.target.value;
     if (!profileId) return;
     const profile = savedProfiles.find((p) => p.id === profileId);
     if (profile) {
+      setSelectedProfile(profile);
       setProfileName(profile.name);
       setUrl(profile.url);
       // setAuthType(profile.auth_type as 'basic' | 'iam'); // User wants to filter by authType, so we assume authType is already set
@@ -49,20 +91,28 @@ export function Login() {
 
   const filteredProfiles = savedProfiles.filter((p) => p.auth_type === authType);
 
+  // Settings the form does not show (API key, OAuth2, TLS, Cloud ID, SSH,
+  // proxy, ...) are kept from the selected saved profile
+  const buildProfile = (id: string, name: string) => ({
+    ...(selectedProfile?.auth_type === authType ? selectedProfile : {}),
+    id,
+    name,
+    url,
+    auth_type: authType,
+    username: authType === 'basic' ? username : null,
+    password: authType === 'basic' ? password : null,
+    region: authType === 'iam' ? region : null,
+    access_key: authType === 'iam' ? accessKey : null,
+    secret_key: authType === 'iam' ? secretKey : null,
+    // A copy of a shared profile is the user's own
+    read_only: false,
+    source: null,
+  });
+
   const handleTestConnection = async () => {
     setIsLoading(true);
     try {
-      const profile = {
-        id: 'test',
-        name: 'Test Profile',
-        url,
-        auth_type: authType,
-        username: authType === 'basic' ? username : null,
-        password: authType === 'basic' ? password : null,
-        region: authType === 'iam' ? region : null,
-        access_key: authType === 'iam' ? accessKey : null,
-        secret_key: authType === 'iam' ? secretKey : null,
-      };
+      const profile = buildProfile('test', 'Test Profile');
 
       await invoke('test_connection', { profile });
 
@@ -76,7 +126,7 @@ export function Login() {
       addToast({
         type: 'error',
         title: 'Connection Failed',
-        message: String(error),
+        message: describeError(error),
         duration: 5000,
       });
     } finally {
@@ -89,29 +139,18 @@ export function Login() {
     setIsLoading(true);
 
     try {
-      const profile = {
-        id: Date.now().toString(), // In real app, use UUID or let DB generate
-        name: profileName,
-        url,
-        auth_type: authType,
-        username: authType === 'basic' ? username : null,
-        password: authType === 'basic' ? password : null,
-        region: authType === 'iam' ? region : null,
-        access_key: authType === 'iam' ? accessKey : null,
-        secret_key: authType === 'iam' ? secretKey : null,
-      };
+      const profile = buildProfile(
+        Date.now().toString(), // In real app, use UUID or let DB generate
+        profileName,
+      );
 
       // Call Tauri backend to verify connection
-      await invoke('connect_to_cluster', { profile });
+      const session = await invoke<{ session_id: string }>('connect_to_cluster', { profile });
 
       if (saveProfile) {
         await invoke('save_profile', { profile });
         // Refresh profiles
-        invoke('get_profiles')
-          .then((profiles: any) => {
-            setSavedProfiles(profiles);
-          })
-          .catch(console.error);
+        loadProfiles();
       }
 
       login({
@@ -121,6 +160,7 @@ export function Login() {
         authType: profile.auth_type as 'basic' | 'iam',
         username: profile.username || undefined,
         region: profile.region || undefined,
+        sessionId: session.session_id,
       });
 
       addToast({
@@ -133,7 +173,7 @@ export function Login() {
       addToast({
         type: 'error',
         title: 'Connection Failed',
-        message: String(error),
+        message: describeError(error),
         duration: 5000,
       });
     } finally {
@@ -179,6 +219,31 @@ export function Login() {
         </div>
 
         <form onSubmit={handleConnect} className="space-y-4">
+          {storeLocked && (
+            <div>
+              <label className="mb-1 block text-xs font-medium text-macos-textSecondary">
+                Master Password
+              </label>
+              <div className="flex gap-2">
+                <input
+                  type="password"
+                  value={masterPassword}
+                  onChange={(e) => setMasterPassword(e.target.value)}
+                  placeholder="Unlock saved profiles"
+                  className="flex-1 rounded-md border border-macos-border bg-macos-input px-3 py-2 text-sm text-macos-text focus:border-macos-active focus:outline-none focus:ring-1 focus:ring-macos-active"
+                />
+                <button
+                  type="button"
+                  onClick={handleUnlock}
+                  disabled={!masterPassword}
+                  className="rounded-md border border-macos-border px-3 text-sm font-medium text-macos-text hover:bg-black/5 dark:hover:bg-white/10 disabled:opacity-50"
+                >
+                  Unlock
+                </button>
+              </div>
+            </div>
+          )}
+
           {filteredProfiles.length > 0 && (
             <div>
               <label className="mb-1 block text-xs font-medium text-macos-textSecondary">
@@ -186,8 +251,8 @@ export function Login() {
               </label>
               <select
                 onChange={handleProfileSelect}
+                value={selectedProfile?.auth_type === authType ? selectedProfile.id : ''}
                 className="w-full rounded-md border border-macos-border bg-macos-input p-2 text-sm text-macos-text focus:border-macos-active focus:outline-none focus:ring-1 focus:ring-macos-active"
-                defaultValue=""
               >
                 <option value="" disabled>
                   Select a profile...